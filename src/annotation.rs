@@ -0,0 +1,245 @@
+//! Arrows and circled squares drawn over the board, stored in PGN comments
+//! with the `[%cal Ge2e4]` and `[%csl Rd5]` commands.
+
+/// The colors understood by the `%cal` and `%csl` commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl AnnotationColor {
+    fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'G' => Some(AnnotationColor::Green),
+            'R' => Some(AnnotationColor::Red),
+            'Y' => Some(AnnotationColor::Yellow),
+            'B' => Some(AnnotationColor::Blue),
+            _ => None,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            AnnotationColor::Green => 'G',
+            AnnotationColor::Red => 'R',
+            AnnotationColor::Yellow => 'Y',
+            AnnotationColor::Blue => 'B',
+        }
+    }
+}
+
+/// Cells are given as [file, rank], both from 0 to 7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Annotation {
+    Arrow {
+        from: [u8; 2],
+        to: [u8; 2],
+        color: AnnotationColor,
+    },
+    Circle {
+        cell: [u8; 2],
+        color: AnnotationColor,
+    },
+}
+
+impl Annotation {
+    /// Two annotations with the same cells replace each other, whatever
+    /// their colors.
+    pub fn same_cells(&self, other: &Annotation) -> bool {
+        match (self, other) {
+            (Annotation::Arrow { from, to, .. }, Annotation::Arrow { from: f, to: t, .. }) => {
+                from == f && to == t
+            }
+            (Annotation::Circle { cell, .. }, Annotation::Circle { cell: c, .. }) => cell == c,
+            _ => false,
+        }
+    }
+}
+
+/// Adds the annotation, or removes it if it was already there with the
+/// same color. An annotation with the same cells but another color is
+/// replaced.
+pub fn toggle(annotations: &mut Vec<Annotation>, annotation: Annotation) {
+    let existing = annotations.iter().position(|a| a.same_cells(&annotation));
+    match existing {
+        Some(index) if annotations[index] == annotation => {
+            annotations.remove(index);
+        }
+        Some(index) => annotations[index] = annotation,
+        None => annotations.push(annotation),
+    }
+}
+
+/// Parses a cell given in algebraic notation, such as "e4", as [file, rank].
+pub fn parse_cell(text: &str) -> Option<[u8; 2]> {
+    let mut chars = text.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some([file as u8 - b'a', rank as u8 - b'1'])
+}
+
+/// The algebraic notation of a cell given as [file, rank].
+pub fn cell_text(cell: [u8; 2]) -> String {
+    format!("{}{}", (b'a' + cell[0]) as char, (b'1' + cell[1]) as char)
+}
+
+fn parse_arrow(text: &str) -> Option<Annotation> {
+    if text.len() != 5 || !text.is_ascii() {
+        return None;
+    }
+    Some(Annotation::Arrow {
+        color: AnnotationColor::from_letter(text.chars().next()?)?,
+        from: parse_cell(&text[1..3])?,
+        to: parse_cell(&text[3..5])?,
+    })
+}
+
+fn parse_circle(text: &str) -> Option<Annotation> {
+    if text.len() != 3 || !text.is_ascii() {
+        return None;
+    }
+    Some(Annotation::Circle {
+        color: AnnotationColor::from_letter(text.chars().next()?)?,
+        cell: parse_cell(&text[1..3])?,
+    })
+}
+
+/// Splits a PGN comment into its plain text and the annotations of its
+/// `%cal` and `%csl` commands. Other commands, such as `%clk`, are kept in
+/// the text. Malformed entries are skipped.
+pub fn parse_comment(comment: &str) -> (String, Vec<Annotation>) {
+    let mut text = String::new();
+    let mut annotations: Vec<Annotation> = Vec::new();
+    let mut rest = comment;
+
+    while let Some(start) = rest.find("[%") {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let command = &rest[start + 2..end];
+        let mut parts = command.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or_default();
+        let arguments = parts.next().unwrap_or_default();
+
+        let parser: Option<fn(&str) -> Option<Annotation>> = match name {
+            "cal" => Some(parse_arrow),
+            "csl" => Some(parse_circle),
+            _ => None,
+        };
+        match parser {
+            Some(parser) => {
+                text.push_str(&rest[..start]);
+                annotations.extend(arguments.split(',').filter_map(|a| parser(a.trim())));
+            }
+            None => text.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+
+    (
+        text.split_whitespace().collect::<Vec<_>>().join(" "),
+        annotations,
+    )
+}
+
+/// The `%csl` and `%cal` commands for the annotations, or an empty string
+/// if there are none.
+pub fn format_commands(annotations: &[Annotation]) -> String {
+    let mut circles: Vec<String> = Vec::new();
+    let mut arrows: Vec<String> = Vec::new();
+    for annotation in annotations {
+        match *annotation {
+            Annotation::Circle { cell, color } => {
+                circles.push(format!("{}{}", color.letter(), cell_text(cell)))
+            }
+            Annotation::Arrow { from, to, color } => arrows.push(format!(
+                "{}{}{}",
+                color.letter(),
+                cell_text(from),
+                cell_text(to)
+            )),
+        }
+    }
+
+    let mut res = String::new();
+    if !circles.is_empty() {
+        res.push_str(&format!("[%csl {}]", circles.join(",")));
+    }
+    if !arrows.is_empty() {
+        res.push_str(&format!("[%cal {}]", arrows.join(",")));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_commands_and_keeps_other_ones() {
+        let comment = "Strong. [%csl Gd4,Re5,Bf7] [%clk 0:02:55] [%cal Ye2e4,Bg1f3,Xa1a2] Next.";
+        let (text, annotations) = parse_comment(comment);
+        assert_eq!(text, "Strong. [%clk 0:02:55] Next.");
+        assert_eq!(
+            annotations,
+            vec![
+                Annotation::Circle {
+                    cell: [3, 3],
+                    color: AnnotationColor::Green
+                },
+                Annotation::Circle {
+                    cell: [4, 4],
+                    color: AnnotationColor::Red
+                },
+                Annotation::Circle {
+                    cell: [5, 6],
+                    color: AnnotationColor::Blue
+                },
+                Annotation::Arrow {
+                    from: [4, 1],
+                    to: [4, 3],
+                    color: AnnotationColor::Yellow
+                },
+                Annotation::Arrow {
+                    from: [6, 0],
+                    to: [5, 2],
+                    color: AnnotationColor::Blue
+                },
+            ]
+        );
+
+        let commands = format_commands(&annotations);
+        assert_eq!(commands, "[%csl Gd4,Re5,Bf7][%cal Ye2e4,Bg1f3]");
+        assert_eq!(parse_comment(&commands), (String::new(), annotations));
+        assert_eq!(format_commands(&[]), "");
+    }
+
+    #[test]
+    fn toggles_annotations() {
+        let green = Annotation::Arrow {
+            from: [4, 1],
+            to: [4, 3],
+            color: AnnotationColor::Green,
+        };
+        let red = Annotation::Arrow {
+            from: [4, 1],
+            to: [4, 3],
+            color: AnnotationColor::Red,
+        };
+        let mut annotations = Vec::new();
+        toggle(&mut annotations, green);
+        assert_eq!(annotations, vec![green]);
+        toggle(&mut annotations, red);
+        assert_eq!(annotations, vec![red]);
+        toggle(&mut annotations, red);
+        assert!(annotations.is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! Fischer Random chess. Its castling rights are read by `PositionSetup`
+//! and its castling is played by the rules.
+
+/// The rules a game is played with, as given by the Variant tag of PGN
+/// files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Standard,
+    Chess960,
+}
+
+impl Variant {
+    /// Reads the value of a Variant tag, None for variants which are not
+    /// supported.
+    pub fn from_tag(value: &str) -> Option<Self> {
+        let name: String = value
+            .chars()
+            .filter(|letter| letter.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        match name.as_str() {
+            "" | "standard" | "chess" => Some(Variant::Standard),
+            "chess960" | "fischerandom" | "fischerrandom" | "frc" => Some(Variant::Chess960),
+            _ => None,
+        }
+    }
+
+    /// The value of the Variant tag, which standard games don't need.
+    pub fn tag(self) -> Option<&'static str> {
+        match self {
+            Variant::Standard => None,
+            Variant::Chess960 => Some("Chess960"),
+        }
+    }
+}
+
+/// Knights files among the five cells left once bishops and queen are
+/// placed, in the order of the Scharnagl numbering.
+const KNIGHTS_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// The FEN string of the start position with the given Scharnagl number,
+/// from 0 to 959, 518 being the start position of standard chess.
+pub fn start_position(index: u16) -> Result<String, String> {
+    if index > 959 {
+        return Err(format!("{} is not a Chess960 position number", index));
+    }
+
+    let mut back_rank: [Option<char>; 8] = [None; 8];
+    let mut index = index as usize;
+    back_rank[2 * (index % 4) + 1] = Some('b');
+    index /= 4;
+    back_rank[2 * (index % 4)] = Some('b');
+    index /= 4;
+
+    let mut place_on_empty = |nth: usize, piece: char| {
+        if let Some(cell) = back_rank.iter_mut().filter(|cell| cell.is_none()).nth(nth) {
+            *cell = Some(piece);
+        }
+    };
+    place_on_empty(index % 6, 'q');
+    let (first_knight, second_knight) = KNIGHTS_PLACEMENTS[index / 6];
+    // The second knight first, as placing the first one would shift the
+    // empty cells after it.
+    place_on_empty(second_knight, 'n');
+    place_on_empty(first_knight, 'n');
+    for piece in ['r', 'k', 'r'].iter() {
+        place_on_empty(0, *piece);
+    }
+
+    let black_pieces: String = back_rank.iter().map(|piece| piece.unwrap_or('1')).collect();
+    Ok(format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        black_pieces,
+        black_pieces.to_uppercase()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position_setup::{CastlingRight, PositionSetup, START_POSITION};
+
+    #[test]
+    fn numbers_start_positions() {
+        assert_eq!(
+            start_position(0).unwrap(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        assert_eq!(start_position(518).unwrap(), START_POSITION);
+        assert_eq!(
+            start_position(959).unwrap(),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1"
+        );
+        assert!(start_position(960).is_err());
+        for index in 0..960 {
+            let fen = start_position(index).unwrap();
+            assert!(PositionSetup::from_fen(&fen).unwrap().validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn reads_rook_files() {
+        let shredder = "rk2r3/8/8/8/8/8/8/RK2R3 w EAea - 0 1";
+        let setup = PositionSetup::from_fen(shredder).unwrap();
+        assert_eq!(setup.castling_rook(CastlingRight::WhiteShort), Some(4));
+        assert_eq!(setup.castling_rook(CastlingRight::BlackLong), Some(0));
+        assert_eq!(
+            setup.validate().unwrap(),
+            "rk2r3/8/8/8/8/8/8/RK2R3 w KQkq - 0 1"
+        );
+
+        let x_fen = "1r1k1r1r/8/8/8/8/8/8/1R1K1R1R w Fh - 0 1";
+        let setup = PositionSetup::from_fen(x_fen).unwrap();
+        assert_eq!(setup.castling_rook(CastlingRight::WhiteShort), Some(5));
+        assert_eq!(setup.castling_rook(CastlingRight::BlackShort), Some(7));
+        assert_eq!(setup.fen(), "1r1k1r1r/8/8/8/8/8/8/1R1K1R1R w Fk - 0 1");
+    }
+
+    #[test]
+    fn reads_variant_tags() {
+        assert_eq!(Variant::from_tag("Chess960"), Some(Variant::Chess960));
+        assert_eq!(Variant::from_tag("Fischerandom"), Some(Variant::Chess960));
+        assert_eq!(Variant::from_tag("Standard"), Some(Variant::Standard));
+        assert_eq!(Variant::from_tag("Crazyhouse"), None);
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+use std::fs;
+use std::path::PathBuf;
+
+const APPLICATION_DIRECTORY: &str = "chess-exercises-organizer";
+const CONFIG_FILE: &str = "config.toml";
+
+/// The configuration directory of the application, such as
+/// `~/.config/chess-exercises-organizer` on Linux.
+pub fn config_directory() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(APPLICATION_DIRECTORY))
+}
+
+/// The data directory of the application, such as
+/// `~/.local/share/chess-exercises-organizer` on Linux.
+pub fn data_directory() -> Option<PathBuf> {
+    dirs::data_dir().map(|directory| directory.join(APPLICATION_DIRECTORY))
+}
+
+/// A user board theme. Colors are given as "#rrggbb" or "#rrggbbaa".
+///
+/// ```toml
+/// [[themes]]
+/// name = "Walnut"
+/// light_cell = "#e8c99b"
+/// dark_cell = "#a3714b"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThemeConfig {
+    pub name: String,
+    pub light_cell: Option<String>,
+    pub dark_cell: Option<String>,
+    pub frame: Option<String>,
+    pub coordinates: Option<String>,
+    pub drag_start: Option<String>,
+    pub drag_lines: Option<String>,
+    pub drag_target: Option<String>,
+    pub move_hint: Option<String>,
+    pub last_move: Option<String>,
+    pub check: Option<String>,
+}
+
+/// The content of the `config.toml` file of the configuration directory.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    /// Replaces the images compiled into the executable with the ones found
+    /// in this directory, laid out like `src/graphic/resources`.
+    pub resources_directory: Option<PathBuf>,
+    #[serde(default)]
+    pub themes: Vec<ThemeConfig>,
+}
+
+/// Reads the configuration file. A missing file gives the default
+/// configuration, and a wrong one is reported and ignored.
+pub fn load_config() -> Config {
+    let path = match config_directory() {
+        Some(directory) => directory.join(CONFIG_FILE),
+        None => return Config::default(),
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Config::default(),
+    };
+
+    toml::from_str(&content).unwrap_or_else(|e| {
+        println!(
+            "Wrong configuration file {} : {} !",
+            path.to_string_lossy(),
+            e
+        );
+        Config::default()
+    })
+}
@@ -0,0 +1,338 @@
+//! Exercises: a position to solve, the moves expected from the user and
+//! what the user is asked to achieve, grouped into collections.
+
+use crate::chess960::Variant;
+use crate::pgn::{Game, MoveNode, PgnFile};
+use crate::position_setup::PositionSetup;
+use crate::rules::{Move, Side};
+
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+
+/// What the user must achieve from the start position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    MateIn(u8),
+    WinMaterial,
+    BestMove,
+    HoldDraw,
+}
+
+impl Goal {
+    /// Reads the value of a Goal tag, such as "Mate in 3" or "Hold the draw".
+    pub fn from_tag(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        if let Some(moves) = value.strip_prefix("mate in ") {
+            return moves.trim().parse().ok().map(Goal::MateIn);
+        }
+        match value.as_str() {
+            "win material" => Some(Goal::WinMaterial),
+            "best move" => Some(Goal::BestMove),
+            "hold the draw" | "hold draw" => Some(Goal::HoldDraw),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Goal::MateIn(moves) => write!(f, "Mate in {}", moves),
+            Goal::WinMaterial => write!(f, "Win material"),
+            Goal::BestMove => write!(f, "Best move"),
+            Goal::HoldDraw => write!(f, "Hold the draw"),
+        }
+    }
+}
+
+/// The tags holding the fields of an exercise rather than game data.
+const EXERCISE_TAGS: [&str; 5] = ["UserSide", "Goal", "Tags", "Difficulty", "Source"];
+
+/// Glyphs of the tries refuted by a variation, which are not accepted as
+/// alternatives to the solution: "?", "??" and "?!".
+const MISTAKE_NAGS: [u8; 3] = [2, 4, 6];
+
+/// A successful try at an exercise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attempt {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    /// The wrong moves played before the solution.
+    pub mistakes: u32,
+}
+
+/// A position to solve. The main line of the game is the solution, with
+/// the answers of the opponent. Variations starting with a move of the user
+/// are accepted alternatives, unless their first move is marked as a
+/// mistake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exercise {
+    pub game: Game,
+    pub user_side: Side,
+    pub goal: Goal,
+    pub tags: Vec<String>,
+    /// From 1, the easiest, to 5.
+    pub difficulty: Option<u8>,
+    /// Where the exercise comes from, such as a book and its page.
+    pub source: Option<String>,
+    /// The tries of the user, oldest first.
+    pub history: Vec<Attempt>,
+}
+
+fn start_side(game: &Game) -> Side {
+    let white_turn = PositionSetup::from_fen(&game.start_position)
+        .map(|setup| setup.white_turn)
+        .unwrap_or(true);
+    Side::from_white_turn(white_turn)
+}
+
+/// Mate in the number of moves of the user when the solution ends with
+/// the user's mate, else the best move.
+fn default_goal(game: &Game, user_side: Side) -> Goal {
+    let first_user_ply = if start_side(game) == user_side { 1 } else { 2 };
+    let plies = game.moves.len();
+    let user_mates = plies >= first_user_ply
+        && (plies - first_user_ply) % 2 == 0
+        && game
+            .moves
+            .last()
+            .is_some_and(|node| node.san.ends_with('#'));
+    match u8::try_from((plies.saturating_sub(first_user_ply)) / 2 + 1) {
+        Ok(moves) if user_mates => Goal::MateIn(moves),
+        _ => Goal::BestMove,
+    }
+}
+
+impl Exercise {
+    /// Reads the exercise from the Goal, UserSide, Tags, Difficulty and
+    /// Source tags of the game, which are then removed from the game. The
+    /// user plays the side to move unless told otherwise, and a solution
+    /// ending with the user's mate has a mate goal unless told otherwise.
+    pub fn from_game(mut game: Game) -> Self {
+        let user_side = match game.tag("UserSide") {
+            Some("White") => Side::White,
+            Some("Black") => Side::Black,
+            _ => start_side(&game),
+        };
+        let goal = game
+            .tag("Goal")
+            .and_then(Goal::from_tag)
+            .unwrap_or_else(|| default_goal(&game, user_side));
+        let tags = game
+            .tag("Tags")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let difficulty = game
+            .tag("Difficulty")
+            .and_then(|value| value.parse().ok())
+            .filter(|difficulty| (1..=5).contains(difficulty));
+        let source = game.tag("Source").map(String::from);
+        game.tags
+            .retain(|(name, _)| !EXERCISE_TAGS.contains(&name.as_str()));
+
+        Self {
+            game,
+            user_side,
+            goal,
+            tags,
+            difficulty,
+            source,
+            history: Vec::new(),
+        }
+    }
+
+    /// The game with the fields of the exercise written into its tags, as
+    /// read by `from_game`.
+    pub fn to_game(&self) -> Game {
+        let mut res = self.game.clone();
+        let user_side = match self.user_side {
+            Side::White => "White",
+            Side::Black => "Black",
+        };
+        res.tags
+            .push((String::from("UserSide"), String::from(user_side)));
+        res.tags.push((String::from("Goal"), self.goal.to_string()));
+        if !self.tags.is_empty() {
+            res.tags.push((String::from("Tags"), self.tags.join(", ")));
+        }
+        if let Some(difficulty) = self.difficulty {
+            res.tags
+                .push((String::from("Difficulty"), difficulty.to_string()));
+        }
+        if let Some(ref source) = self.source {
+            res.tags.push((String::from("Source"), source.clone()));
+        }
+        res
+    }
+
+    /// An exercise without solution, where the user plays both sides.
+    pub fn from_position(fen: &str, variant: Variant) -> Result<Self, String> {
+        let game = Game::from_position(fen, variant)?;
+        Ok(Self {
+            user_side: start_side(&game),
+            game,
+            goal: Goal::BestMove,
+            tags: Vec::new(),
+            difficulty: None,
+            source: None,
+            history: Vec::new(),
+        })
+    }
+
+    pub fn has_solution(&self) -> bool {
+        !self.game.moves.is_empty()
+    }
+
+    /// Whether the move played after the given number of moves is the
+    /// user's.
+    fn is_user_ply(&self, ply: usize) -> bool {
+        let side = if ply % 2 == 0 {
+            start_side(&self.game)
+        } else {
+            start_side(&self.game).opponent()
+        };
+        side == self.user_side
+    }
+
+    /// The line of the solution the moves lead to, and the index in this
+    /// line of the next move. None when the moves leave the solution.
+    fn follow(&self, moves: &[Move]) -> Option<(&[MoveNode], usize)> {
+        let mut line: &[MoveNode] = &self.game.moves;
+        let mut index = 0;
+        for (ply, chess_move) in moves.iter().enumerate() {
+            let node = line.get(index)?;
+            if node.chess_move == *chess_move {
+                index += 1;
+                continue;
+            }
+            let user_ply = self.is_user_ply(ply);
+            line = node.variations.iter().find(|variation| {
+                variation.first().is_some_and(|first| {
+                    first.chess_move == *chess_move
+                        && !(user_ply && first.nags.iter().any(|nag| MISTAKE_NAGS.contains(nag)))
+                })
+            })?;
+            index = 1;
+        }
+        Some((line, index))
+    }
+
+    /// Whether the moves played from the start position follow the
+    /// solution or one of its accepted alternatives.
+    pub fn follows_solution(&self, moves: &[Move]) -> bool {
+        self.follow(moves).is_some()
+    }
+
+    /// The answer of the opponent to the moves of the user.
+    pub fn reply(&self, moves: &[Move]) -> Option<Move> {
+        if self.is_user_ply(moves.len()) {
+            return None;
+        }
+        let (line, index) = self.follow(moves)?;
+        line.get(index).map(|node| node.chess_move)
+    }
+
+    /// Whether the moves reached the end of a line of the solution.
+    pub fn is_solved(&self, moves: &[Move]) -> bool {
+        self.has_solution()
+            && self
+                .follow(moves)
+                .is_some_and(|(line, index)| index >= line.len())
+    }
+}
+
+/// Exercises grouped under a name, such as the chapter of a book.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collection {
+    pub name: String,
+    pub exercises: Vec<Exercise>,
+}
+
+impl Collection {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            exercises: Vec::new(),
+        }
+    }
+
+    /// One exercise for each game read from the file.
+    pub fn from_pgn(name: &str, file: PgnFile) -> Self {
+        Self {
+            name: String::from(name),
+            exercises: file.games.into_iter().map(Exercise::from_game).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn;
+    use crate::rules::pleco_rules;
+
+    const EXERCISE: &str = r#"[FEN "4kb1r/p2B1ppp/4qn2/4p1B1/4P3/1Q6/PPP2PPP/2KR4 b k - 0 15"]
+[UserSide "White"]
+[Tags "Sacrifice, Back rank"]
+[Difficulty "2"]
+[Source "Opera game"]
+
+15... Nxd7 16. Qb8+ (16. Qb7 $2 Qd6) (16. Rxd7 Qxd7 17. Qb8+ Qd8) Nxb8 17. Rd8# *
+"#;
+
+    fn moves_of(exercise: &Exercise, sans: &[&str]) -> Vec<Move> {
+        let mut rules = exercise.game.start_rules(pleco_rules);
+        sans.iter()
+            .map(|san| {
+                let chess_move = rules.parse_san(san).unwrap();
+                rules.play(&chess_move);
+                chess_move
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_exercise_tags() {
+        let file = pgn::parse("exercise.pgn", EXERCISE, pleco_rules);
+        let exercise = Exercise::from_game(file.games[0].clone());
+        assert_eq!(exercise.user_side, Side::White);
+        assert_eq!(exercise.goal, Goal::MateIn(2));
+        assert_eq!(exercise.tags, vec!["Sacrifice", "Back rank"]);
+        assert_eq!(exercise.difficulty, Some(2));
+        assert_eq!(exercise.source.as_deref(), Some("Opera game"));
+        assert!(exercise.game.tag("UserSide").is_none());
+        assert_eq!(Exercise::from_game(exercise.to_game()), exercise);
+        assert_eq!(Goal::from_tag("Hold the draw"), Some(Goal::HoldDraw));
+        assert_eq!(
+            Goal::from_tag(&Goal::MateIn(3).to_string()),
+            Some(Goal::MateIn(3))
+        );
+    }
+
+    #[test]
+    fn follows_solution_and_alternatives() {
+        let file = pgn::parse("exercise.pgn", EXERCISE, pleco_rules);
+        let exercise = Exercise::from_game(file.games[0].clone());
+
+        // The opponent plays first.
+        assert_eq!(exercise.reply(&[]), Some(moves_of(&exercise, &["Nxd7"])[0]));
+        let solution = moves_of(&exercise, &["Nxd7", "Qb8+", "Nxb8", "Rd8#"]);
+        assert!(exercise.follows_solution(&solution[..2]));
+        assert_eq!(exercise.reply(&solution[..2]), Some(solution[2]));
+        assert!(!exercise.is_solved(&solution[..3]));
+        assert!(exercise.is_solved(&solution));
+
+        let alternative = moves_of(&exercise, &["Nxd7", "Rxd7"]);
+        assert!(exercise.follows_solution(&alternative));
+        let mistake = moves_of(&exercise, &["Nxd7", "Qb7"]);
+        assert!(!exercise.follows_solution(&mistake));
+    }
+}
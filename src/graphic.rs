@@ -1,5 +1,14 @@
 mod main_window;
 pub use main_window::start;
 
+mod diagram;
+pub use diagram::{render_diagram, DiagramOptions};
+
+mod board_theme;
+
+mod animation;
 mod chess_board;
-mod chess_board_component;
\ No newline at end of file
+mod material_strip;
+mod piece_set;
+mod position_editor;
+mod resources;
@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+use crate::rules::{ChessRules, Piece, PieceKind};
+
+/// How the progress of an animation evolves over its duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseOutQuad,
+    EaseInOutCubic,
+}
+
+impl Easing {
+    /// Maps a linear progress, from 0.0 to 1.0, to the eased one.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOutQuad => t * (2.0 - t),
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let u = -2.0 * t + 2.0;
+                    1.0 - u * u * u / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// A piece sliding from one cell to another. Cells are given as [file, rank].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlidingPiece {
+    pub piece: Piece,
+    pub from: [u8; 2],
+    pub to: [u8; 2],
+}
+
+/// The pieces that moved between two positions, with the pieces that
+/// disappeared (mostly captured ones) fading out on their cells.
+#[derive(Debug, Clone)]
+pub struct PieceAnimation {
+    start: Instant,
+    duration: Duration,
+    easing: Easing,
+    sliding_pieces: Vec<SlidingPiece>,
+    fading_pieces: Vec<(Piece, [u8; 2])>,
+}
+
+fn pieces_of(rules: &dyn ChessRules) -> Vec<Option<Piece>> {
+    (0..64u8)
+        .map(|square| rules.piece_at([square % 8, square / 8]))
+        .collect()
+}
+
+fn cell_of(square: usize) -> [u8; 2] {
+    [(square % 8) as u8, (square / 8) as u8]
+}
+
+fn distance(first: [u8; 2], second: [u8; 2]) -> u32 {
+    let file_distance = (first[0] as i32 - second[0] as i32).abs();
+    let rank_distance = (first[1] as i32 - second[1] as i32).abs();
+    (file_distance * file_distance + rank_distance * rank_distance) as u32
+}
+
+fn is_pawn_of_same_player(pawn: Piece, piece: Piece) -> bool {
+    pawn.kind() == PieceKind::Pawn && pawn.side() == piece.side()
+}
+
+impl PieceAnimation {
+    /// Matches every piece appearing in the new position with the closest
+    /// identical piece leaving the old one. A promoted piece comes from a
+    /// pawn of its side, and unmatched leaving pieces fade out.
+    pub fn new(
+        from: &dyn ChessRules,
+        to: &dyn ChessRules,
+        duration: Duration,
+        easing: Easing,
+    ) -> Self {
+        let old_pieces = pieces_of(from);
+        let new_pieces = pieces_of(to);
+
+        let mut leaving: Vec<(Piece, [u8; 2])> = Vec::new();
+        let mut arriving: Vec<(Piece, [u8; 2])> = Vec::new();
+        for square in 0..64 {
+            if old_pieces[square] == new_pieces[square] {
+                continue;
+            }
+            if let Some(piece) = old_pieces[square] {
+                leaving.push((piece, cell_of(square)));
+            }
+            if let Some(piece) = new_pieces[square] {
+                arriving.push((piece, cell_of(square)));
+            }
+        }
+
+        let mut sliding_pieces: Vec<SlidingPiece> = Vec::new();
+        let mut unmatched: Vec<(Piece, [u8; 2])> = Vec::new();
+        for (piece, to_cell) in arriving {
+            let closest = leaving
+                .iter()
+                .enumerate()
+                .filter(|(_, (leaving_piece, _))| *leaving_piece == piece)
+                .min_by_key(|(_, (_, from_cell))| distance(*from_cell, to_cell))
+                .map(|(index, _)| index);
+            match closest {
+                Some(index) => {
+                    let (_, from_cell) = leaving.remove(index);
+                    sliding_pieces.push(SlidingPiece {
+                        piece,
+                        from: from_cell,
+                        to: to_cell,
+                    });
+                }
+                None => unmatched.push((piece, to_cell)),
+            }
+        }
+        for (piece, to_cell) in unmatched {
+            let pawn = leaving
+                .iter()
+                .position(|(leaving_piece, _)| is_pawn_of_same_player(*leaving_piece, piece));
+            if let Some(index) = pawn {
+                let (_, from_cell) = leaving.remove(index);
+                sliding_pieces.push(SlidingPiece {
+                    piece,
+                    from: from_cell,
+                    to: to_cell,
+                });
+            }
+        }
+
+        Self {
+            start: Instant::now(),
+            duration,
+            easing,
+            sliding_pieces,
+            fading_pieces: leaving,
+        }
+    }
+
+    /// Eased progress, from 0.0 to 1.0, at the given instant.
+    pub fn progress(&self, now: Instant) -> f32 {
+        if self.duration.as_secs_f32() <= 0.0 {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(self.start);
+        self.easing
+            .apply(elapsed.as_secs_f32() / self.duration.as_secs_f32())
+    }
+
+    pub fn is_finished(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) >= self.duration
+    }
+
+    pub fn sliding_pieces(&self) -> &[SlidingPiece] {
+        &self.sliding_pieces
+    }
+
+    pub fn fading_pieces(&self) -> &[(Piece, [u8; 2])] {
+        &self.fading_pieces
+    }
+}
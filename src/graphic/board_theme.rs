@@ -0,0 +1,164 @@
+use iced_native::Color;
+
+use crate::config::ThemeConfig;
+
+/// The colors of a board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardTheme {
+    pub name: String,
+    pub light_cell: Color,
+    pub dark_cell: Color,
+    pub frame: Color,
+    pub coordinates: Color,
+    pub drag_start: Color,
+    pub drag_lines: Color,
+    pub drag_target: Color,
+    pub move_hint: Color,
+    pub last_move: Color,
+    pub check: Color,
+}
+
+impl Default for BoardTheme {
+    fn default() -> Self {
+        Self::brown()
+    }
+}
+
+impl BoardTheme {
+    pub fn brown() -> Self {
+        Self {
+            name: String::from("Brown"),
+            light_cell: Color::from_rgb8(255, 206, 158),
+            dark_cell: Color::from_rgb8(209, 139, 71),
+            frame: Color::from_rgb8(214, 59, 96),
+            coordinates: Color::from_rgb8(255, 199, 0),
+            drag_start: Color::from_rgb8(214, 59, 96),
+            drag_lines: Color::from_rgb8(178, 46, 230),
+            drag_target: Color::from_rgb8(112, 209, 35),
+            move_hint: Color::from_rgba8(20, 85, 30, 0.5),
+            last_move: Color::from_rgba8(155, 199, 0, 0.45),
+            check: Color::from_rgb8(230, 20, 20),
+        }
+    }
+
+    pub fn blue() -> Self {
+        Self {
+            name: String::from("Blue"),
+            light_cell: Color::from_rgb8(222, 227, 230),
+            dark_cell: Color::from_rgb8(140, 162, 173),
+            frame: Color::from_rgb8(40, 62, 92),
+            coordinates: Color::from_rgb8(230, 236, 240),
+            drag_start: Color::from_rgb8(96, 150, 200),
+            drag_lines: Color::from_rgb8(120, 140, 210),
+            drag_target: Color::from_rgb8(80, 190, 160),
+            move_hint: Color::from_rgba8(20, 50, 90, 0.5),
+            last_move: Color::from_rgba8(100, 170, 230, 0.45),
+            check: Color::from_rgb8(230, 20, 20),
+        }
+    }
+
+    pub fn green() -> Self {
+        Self {
+            name: String::from("Green"),
+            light_cell: Color::from_rgb8(238, 238, 210),
+            dark_cell: Color::from_rgb8(118, 150, 86),
+            frame: Color::from_rgb8(49, 46, 43),
+            coordinates: Color::from_rgb8(238, 238, 210),
+            drag_start: Color::from_rgb8(186, 202, 68),
+            drag_lines: Color::from_rgb8(150, 170, 110),
+            drag_target: Color::from_rgb8(246, 246, 105),
+            move_hint: Color::from_rgba8(0, 0, 0, 0.2),
+            last_move: Color::from_rgba8(246, 246, 105, 0.5),
+            check: Color::from_rgb8(230, 20, 20),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            name: String::from("High contrast"),
+            light_cell: Color::WHITE,
+            dark_cell: Color::from_rgb8(90, 90, 90),
+            frame: Color::BLACK,
+            coordinates: Color::from_rgb8(255, 255, 0),
+            drag_start: Color::from_rgb8(255, 0, 255),
+            drag_lines: Color::from_rgb8(0, 200, 255),
+            drag_target: Color::from_rgb8(0, 255, 0),
+            move_hint: Color::from_rgba8(255, 0, 0, 0.8),
+            last_move: Color::from_rgba8(0, 120, 255, 0.6),
+            check: Color::from_rgb8(255, 0, 0),
+        }
+    }
+
+    /// The themes shipped with the application.
+    pub fn presets() -> Vec<Self> {
+        vec![
+            Self::brown(),
+            Self::blue(),
+            Self::green(),
+            Self::high_contrast(),
+        ]
+    }
+
+    /// Builds a theme from the configuration file. Missing colors are taken
+    /// from the brown theme.
+    pub fn from_config(config: &ThemeConfig) -> Result<Self, String> {
+        let base = Self::brown();
+        let color = |value: &Option<String>, default: Color| match value {
+            Some(value) => parse_color(value),
+            None => Ok(default),
+        };
+
+        Ok(Self {
+            name: config.name.clone(),
+            light_cell: color(&config.light_cell, base.light_cell)?,
+            dark_cell: color(&config.dark_cell, base.dark_cell)?,
+            frame: color(&config.frame, base.frame)?,
+            coordinates: color(&config.coordinates, base.coordinates)?,
+            drag_start: color(&config.drag_start, base.drag_start)?,
+            drag_lines: color(&config.drag_lines, base.drag_lines)?,
+            drag_target: color(&config.drag_target, base.drag_target)?,
+            move_hint: color(&config.move_hint, base.move_hint)?,
+            last_move: color(&config.last_move, base.last_move)?,
+            check: color(&config.check, base.check)?,
+        })
+    }
+
+    /// The presets followed by the valid themes of the configuration file.
+    pub fn all(configs: &[ThemeConfig]) -> Vec<Self> {
+        let mut res = Self::presets();
+        for config in configs {
+            match Self::from_config(config) {
+                Ok(theme) => res.push(theme),
+                Err(e) => println!("Wrong theme {} : {} !", config.name, e),
+            }
+        }
+        res
+    }
+}
+
+/// Parses a "#rrggbb" or "#rrggbbaa" color.
+fn parse_color(text: &str) -> Result<Color, String> {
+    let hex = text
+        .strip_prefix('#')
+        .ok_or_else(|| format!("color {} should start with #", text))?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+        return Err(format!("color {} should be #rrggbb or #rrggbbaa", text));
+    }
+
+    let component = |index: usize| {
+        u8::from_str_radix(&hex[index..index + 2], 16)
+            .map_err(|_| format!("color {} is not hexadecimal", text))
+    };
+    let alpha = if hex.len() == 8 {
+        component(6)? as f32 / 255.0
+    } else {
+        1.0
+    };
+
+    Ok(Color::from_rgba8(
+        component(0)?,
+        component(2)?,
+        component(4)?,
+        alpha,
+    ))
+}
@@ -1,69 +1,184 @@
 use iced_graphics::{
+    triangle::{Mesh2D, Vertex2D},
     Backend, Defaults, Font, HorizontalAlignment, Primitive, Renderer, VerticalAlignment,
 };
 use iced_native::{
     event::{Event, Status},
-    layout,
-    widget::svg::Handle,
-    Background, Clipboard, Color, Element, Hasher, Layout, Length, Point, Rectangle, Size, Vector,
-    Widget,
+    keyboard::{Event as KeyboardEvent, KeyCode, Modifiers},
+    layout, Background, Clipboard, Color, Column, Element, Hasher, Layout, Length, Point,
+    Rectangle, Size, Vector, Widget,
     {
         mouse,
         mouse::{Button as MouseButton, Event as MouseEvent},
     },
 };
-use pleco::core::{sq::SQ, Piece, Player};
-use pleco::Board;
 
-use std::collections::HashMap;
-use std::fs;
+use super::animation::{Easing, PieceAnimation};
+use super::board_theme::BoardTheme;
+use super::material_strip::MaterialStrip;
+use super::piece_set::PieceSet;
+use crate::annotation::{self, Annotation, AnnotationColor};
+use crate::position_setup::PositionSetup;
+use crate::rules::{ChessRules, Move, Piece, PieceKind, Side};
+
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-fn load_assets() -> Rc<HashMap<String, Handle>> {
-    let assets_dir = format!(
-        "{}/src/graphic/resources/merida",
-        env!("CARGO_MANIFEST_DIR")
-    );
-    let files = fs::read_dir(assets_dir.clone())
-        .unwrap_or_else(|e| panic!("Couldn't read directory {}: {}", assets_dir, e))
-        .map(|f| f.unwrap())
-        .filter(|f| f.metadata().unwrap().is_file());
-    let mut res: HashMap<String, Handle> = HashMap::new();
-    for file in files {
-        let svg = Handle::from_path(file.path());
-        res.insert(
-            file.path()
-                .file_stem()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            svg,
-        );
+mod setup_editor;
+
+pub fn color_for_annotation(color: AnnotationColor) -> Color {
+    match color {
+        AnnotationColor::Green => Color::from_rgba8(21, 120, 27, 0.8),
+        AnnotationColor::Red => Color::from_rgba8(136, 32, 32, 0.8),
+        AnnotationColor::Yellow => Color::from_rgba8(230, 143, 0, 0.8),
+        AnnotationColor::Blue => Color::from_rgba8(0, 48, 136, 0.8),
+    }
+}
+
+/// Shift or control draws in red, alt in blue, and both in yellow.
+fn annotation_color_for_modifiers(modifiers: Modifiers) -> AnnotationColor {
+    let red = modifiers.shift || modifiers.control;
+    let blue = modifiers.alt || modifiers.logo;
+    match (red, blue) {
+        (true, true) => AnnotationColor::Yellow,
+        (true, false) => AnnotationColor::Red,
+        (false, true) => AnnotationColor::Blue,
+        (false, false) => AnnotationColor::Green,
+    }
+}
+
+/// Pieces offered by the promotion overlay, from the target square outwards.
+const PROMOTION_PIECES: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+
+fn cell_name(cell: [u8; 2]) -> String {
+    let ascii_lower_a = 97u8;
+    let ascii_digit_1 = 49u8;
+
+    format!(
+        "{}{}",
+        (ascii_lower_a + cell[0]) as char,
+        (ascii_digit_1 + cell[1]) as char,
+    )
+}
+
+fn status_of(captured: bool) -> Status {
+    if captured {
+        Status::Captured
+    } else {
+        Status::Ignored
+    }
+}
+
+/// Why a dropped piece could not be moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMoveReason {
+    NotYourPiece,
+    KingWouldBeInCheck,
+    PieceCannotMoveThere,
+    CastlingNotAllowed,
+}
+
+impl std::fmt::Display for IllegalMoveReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            IllegalMoveReason::NotYourPiece => "not your piece",
+            IllegalMoveReason::KingWouldBeInCheck => "king would be in check",
+            IllegalMoveReason::PieceCannotMoveThere => "piece cannot move there",
+            IllegalMoveReason::CastlingNotAllowed => "castling is not allowed",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// An illegal drop, with both cells given in algebraic notation (e.g. "e2").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalMove {
+    pub from: String,
+    pub to: String,
+    pub reason: IllegalMoveReason,
+}
+
+/// A drag given up with Escape, a right click or a release off the board,
+/// after which the piece slides back to its cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DragCancelled {
+    /// The cell of the dragged piece, in algebraic notation.
+    pub from: String,
+    /// How long the piece was held, from the press to the cancellation.
+    pub held: Duration,
+}
+
+/// Which pieces the user may move. Annotations can always be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionMode {
+    BothSides,
+    WhiteOnly,
+    BlackOnly,
+    ViewOnly,
+}
+
+impl InteractionMode {
+    fn allows(self, side: Side) -> bool {
+        match self {
+            InteractionMode::BothSides => true,
+            InteractionMode::WhiteOnly => side == Side::White,
+            InteractionMode::BlackOnly => side == Side::Black,
+            InteractionMode::ViewOnly => false,
+        }
     }
-    Rc::new(res)
 }
 
-fn asset_name_for_piece(piece: &Piece) -> String {
-    match *piece {
-        Piece::WhitePawn => String::from("wP"),
-        Piece::WhiteKnight => String::from("wN"),
-        Piece::WhiteBishop => String::from("wB"),
-        Piece::WhiteRook => String::from("wR"),
-        Piece::WhiteQueen => String::from("wQ"),
-        Piece::WhiteKing => String::from("wK"),
-        Piece::BlackPawn => String::from("bP"),
-        Piece::BlackKnight => String::from("bN"),
-        Piece::BlackBishop => String::from("bB"),
-        Piece::BlackRook => String::from("bR"),
-        Piece::BlackQueen => String::from("bQ"),
-        Piece::BlackKing => String::from("bK"),
-        _ => String::default(),
+enum DropOutcome {
+    Ignored,
+    Moved,
+    Illegal(IllegalMove),
+    Cancelled(DragCancelled),
+}
+
+struct PromotionState {
+    start_cell: [u8; 2],
+    end_cell: [u8; 2],
+    side: Side,
+}
+
+/// Distance, in pixels, the cursor must travel with the button held before a
+/// press on a piece turns into a drag instead of a click.
+const DRAG_THRESHOLD: f32 = 4.0;
+
+const SNAP_BACK_DURATION: Duration = Duration::from_millis(150);
+
+/// A piece returning to its cell from where its drag was cancelled.
+struct SnapBack {
+    piece: Piece,
+    cell: [u8; 2],
+    /// The top left corner of the piece when released, relative to the
+    /// board.
+    offset: Vector,
+    start: Instant,
+}
+
+impl SnapBack {
+    fn progress(&self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.start);
+        Easing::EaseOutQuad.apply(elapsed.as_secs_f32() / SNAP_BACK_DURATION.as_secs_f32())
+    }
+
+    fn is_finished(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) >= SNAP_BACK_DURATION
     }
 }
 
+#[derive(Default)]
 struct DragAndDropState {
     active: bool,
+    dragging: bool,
+    press_location: Option<[f32; 2]>,
+    press_time: Option<Instant>,
     start_cell: Option<[u8; 2]>,
     end_cell: Option<[u8; 2]>,
     moved_piece: Option<Piece>,
@@ -73,6 +188,9 @@ struct DragAndDropState {
 impl DragAndDropState {
     fn reset(&mut self) {
         self.active = false;
+        self.dragging = false;
+        self.press_location = None;
+        self.press_time = None;
         self.start_cell = None;
         self.end_cell = None;
         self.moved_piece = None;
@@ -80,67 +198,264 @@ impl DragAndDropState {
     }
 }
 
-pub struct ChessBoard<Message> {
-    board: Board,
-    cells_size: f32,
-    piece_assets: Rc<HashMap<String, Handle>>,
-    reversed: bool,
+/// The interaction state of a board, kept by the application between views.
+#[derive(Default)]
+pub struct State {
     dnd_state: DragAndDropState,
+    selected_cell: Option<[u8; 2]>,
+    last_move: Option<([u8; 2], [u8; 2])>,
+    promotion_state: Option<PromotionState>,
+    annotation_start: Option<[u8; 2]>,
+    modifiers: Modifiers,
+    snap_back: Option<SnapBack>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the selection, the pending promotion and the last move, for
+    /// a position which doesn't follow from the previous one.
+    pub fn reset(&mut self) {
+        self.dnd_state.reset();
+        self.selected_cell = None;
+        self.last_move = None;
+        self.promotion_state = None;
+        self.annotation_start = None;
+        self.snap_back = None;
+    }
+
+    /// Whether a piece is still sliding back after a cancelled drag, in which
+    /// case the board must be redrawn until it is back.
+    pub fn is_animating(&self, now: Instant) -> bool {
+        self.snap_back
+            .as_ref()
+            .is_some_and(|snap_back| !snap_back.is_finished(now))
+    }
+}
+
+/// A board showing the position given by the application, which is told
+/// about the moves played through the callbacks.
+pub struct ChessBoard<'a, Message> {
+    rules: Box<dyn ChessRules>,
+    min_size: f32,
+    max_size: f32,
+    piece_set: Rc<PieceSet>,
+    reversed: bool,
+    state: &'a mut State,
+    theme: BoardTheme,
+    annotations: Vec<Annotation>,
+    animation: Option<PieceAnimation>,
+    show_move_hints: bool,
+    interaction_mode: InteractionMode,
+    setup: Option<PositionSetup>,
     on_position_changed: Option<Box<dyn Fn(String) -> Message>>,
+    on_illegal_move: Option<Box<dyn Fn(IllegalMove) -> Message>>,
+    on_drag_cancelled: Option<Box<dyn Fn(DragCancelled) -> Message>>,
+    on_annotations_changed: Option<Box<dyn Fn(Vec<Annotation>) -> Message>>,
+    on_setup_changed: Option<Box<dyn Fn(PositionSetup) -> Message>>,
 }
 
-impl<Message> ChessBoard<Message> {
-    pub fn new(cells_size: f32, reversed: bool, position: String) -> Self {
-        let piece_assets = load_assets();
-        let board = Board::from_fen(position.as_str());
-        let board = match board {
-            Ok(board) => board,
-            Err(_) => {
-                println!("Wrong position : {} !", position);
-                Board::start_pos()
-            }
-        };
+impl<'a, Message> ChessBoard<'a, Message> {
+    /// A board playing the moves the given rules allow, drawn with the
+    /// piece set shared by the application.
+    pub fn new(state: &'a mut State, rules: Box<dyn ChessRules>, piece_set: Rc<PieceSet>) -> Self {
         Self {
-            cells_size,
-            piece_assets,
-            reversed,
-            board,
-            dnd_state: DragAndDropState {
-                active: false,
-                start_cell: None,
-                end_cell: None,
-                moved_piece: None,
-                moved_piece_location: None,
-            },
+            min_size: 180.0,
+            max_size: f32::INFINITY,
+            piece_set,
+            reversed: false,
+            rules,
+            state,
+            theme: BoardTheme::default(),
+            annotations: Vec::new(),
+            animation: None,
+            show_move_hints: true,
+            interaction_mode: InteractionMode::BothSides,
+            setup: None,
             on_position_changed: None,
+            on_illegal_move: None,
+            on_drag_cancelled: None,
+            on_annotations_changed: None,
+            on_setup_changed: None,
         }
     }
 
+    /// A board editing the position instead of playing moves: pieces are
+    /// dragged from a palette under the board, dragged off the board or right
+    /// clicked to be removed.
+    ///
+    /// The rules are the ones of the position being replaced, the board
+    /// showing the setup instead.
+    pub fn setup_editor(
+        state: &'a mut State,
+        setup: PositionSetup,
+        rules: Box<dyn ChessRules>,
+        piece_set: Rc<PieceSet>,
+    ) -> Self {
+        let mut res = Self::new(state, rules, piece_set);
+        res.setup = Some(setup);
+        res
+    }
+
+    /// Black at the bottom.
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// The smallest side of the board, coordinates frame included. The board
+    /// overflows its limits rather than getting smaller.
+    pub fn min_size(mut self, min_size: f32) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// The largest side of the board, coordinates frame included.
+    pub fn max_size(mut self, max_size: f32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
     pub fn on_position_changed(mut self, message: Box<dyn Fn(String) -> Message>) -> Self {
         self.on_position_changed = Some(message);
         self
     }
 
+    /// Whether the legal destinations of the dragged piece are shown. Should be
+    /// turned off in exercise mode, where they would give the answer away.
+    pub fn show_move_hints(mut self, show_move_hints: bool) -> Self {
+        self.show_move_hints = show_move_hints;
+        self
+    }
+
+    /// Restricts the pieces the user may move, for example to the side
+    /// played in an exercise while the program answers with the other one.
+    pub fn interaction_mode(mut self, interaction_mode: InteractionMode) -> Self {
+        self.interaction_mode = interaction_mode;
+        self
+    }
+
+    pub fn theme(mut self, theme: BoardTheme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn on_illegal_move(mut self, message: Box<dyn Fn(IllegalMove) -> Message>) -> Self {
+        self.on_illegal_move = Some(message);
+        self
+    }
+
+    pub fn on_drag_cancelled(mut self, message: Box<dyn Fn(DragCancelled) -> Message>) -> Self {
+        self.on_drag_cancelled = Some(message);
+        self
+    }
+
+    /// Arrows and circles drawn over the board, for example loaded from the
+    /// comment of a PGN move.
+    pub fn annotations(mut self, annotations: Vec<Annotation>) -> Self {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Animates the pieces from the previous position to the current one,
+    /// while the animation is not finished.
+    pub fn animation(mut self, animation: Option<PieceAnimation>) -> Self {
+        self.animation = animation;
+        self
+    }
+
+    pub fn on_annotations_changed(
+        mut self,
+        message: Box<dyn Fn(Vec<Annotation>) -> Message>,
+    ) -> Self {
+        self.on_annotations_changed = Some(message);
+        self
+    }
+
+    /// Called with every change made in the setup editor, the position
+    /// being legal or not. Legal positions are also given as FEN strings to
+    /// `on_position_changed`.
+    pub fn on_setup_changed(mut self, message: Box<dyn Fn(PositionSetup) -> Message>) -> Self {
+        self.on_setup_changed = Some(message);
+        self
+    }
+
+    /// The board between the captured material of both sides, each strip
+    /// next to the pieces of its side.
+    pub fn with_material_strips<B>(self) -> Column<'a, Message, Renderer<B>>
+    where
+        Message: 'a + Clone,
+        B: 'a + Backend,
+    {
+        let (top_side, bottom_side) = if self.reversed {
+            (Side::White, Side::Black)
+        } else {
+            (Side::Black, Side::White)
+        };
+        let top_strip = MaterialStrip::new(self.rules.as_ref(), top_side, self.piece_set.clone());
+        let bottom_strip =
+            MaterialStrip::new(self.rules.as_ref(), bottom_side, self.piece_set.clone());
+
+        Column::new()
+            .spacing(5)
+            .height(Length::Fill)
+            .push(top_strip)
+            .push(self)
+            .push(bottom_strip)
+    }
+
+    fn piece_at(&self, cell: [u8; 2]) -> Option<Piece> {
+        match self.setup {
+            Some(ref setup) => setup.piece_at(cell),
+            None => self.rules.piece_at(cell),
+        }
+    }
+
+    fn is_white_turn(&self) -> bool {
+        match self.setup {
+            Some(ref setup) => setup.white_turn,
+            None => self.rules.side_to_move() == Side::White,
+        }
+    }
+
+    /// The cells fill the board, apart from a frame half a cell wide
+    /// holding the coordinates.
+    fn get_cells_size(&self, layout: &Layout<'_>) -> f32 {
+        layout.bounds().width / 9.0
+    }
+
     fn get_background_primitive(&self, layout: &Layout<'_>) -> Primitive {
         Primitive::Quad {
             bounds: layout.bounds(),
-            background: Background::Color(Color::from_rgb8(214, 59, 96)),
+            background: Background::Color(self.theme.frame),
             border_radius: 0.0,
             border_width: 0.0,
             border_color: Color::TRANSPARENT,
         }
     }
 
+    fn get_cell_color(&self, cell: [u8; 2]) -> Color {
+        let [file, rank] = cell;
+        if (file + rank) % 2 == 1 {
+            self.theme.light_cell
+        } else {
+            self.theme.dark_cell
+        }
+    }
+
     fn get_cells_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
         let mut res: Vec<Primitive> = Vec::new();
 
-        let mut start_coordinates: Option<[u8; 2]> = None;
+        let mut start_coordinates: Option<[u8; 2]> = self.state.selected_cell;
         let mut end_coordinates: Option<[u8; 2]> = None;
-        if self.dnd_state.active {
-            if let Some(start_cell) = self.dnd_state.start_cell {
+        if self.state.dnd_state.dragging {
+            if let Some(start_cell) = self.state.dnd_state.start_cell {
                 start_coordinates = Some(start_cell);
             }
-            if let Some(end_cell) = self.dnd_state.end_cell {
+            if let Some(end_cell) = self.state.dnd_state.end_cell {
                 end_coordinates = Some(end_cell);
             }
         }
@@ -149,30 +464,25 @@ impl<Message> ChessBoard<Message> {
             let rank = if self.reversed { row } else { 7 - row };
             for col in 0..8 {
                 let file = if self.reversed { 7 - col } else { col };
-                let is_white_cell = (row + col) % 2 == 0;
-                let std_background = Background::Color(if is_white_cell {
-                    Color::from_rgb8(255, 206, 158)
-                } else {
-                    Color::from_rgb8(209, 139, 71)
-                });
+                let std_background = Background::Color(self.get_cell_color([file, rank]));
                 let mut background = std_background;
 
                 if let Some([end_file, end_rank]) = end_coordinates {
                     if file == end_file || rank == end_rank {
-                        background = Background::Color(Color::from_rgb8(178, 46, 230));
+                        background = Background::Color(self.theme.drag_lines);
                     }
                 }
-                if Some([file as u8, rank as u8]) == start_coordinates {
-                    background = Background::Color(Color::from_rgb8(214, 59, 96));
+                if Some([file, rank]) == start_coordinates {
+                    background = Background::Color(self.theme.drag_start);
                 }
-                if Some([file as u8, rank as u8]) == end_coordinates {
-                    background = Background::Color(Color::from_rgb8(112, 209, 35));
+                if Some([file, rank]) == end_coordinates {
+                    background = Background::Color(self.theme.drag_target);
                 }
 
-                let x = self.cells_size * ((col as f32) + 0.5);
-                let y = self.cells_size * ((row as f32) + 0.5);
+                let x = cells_size * ((col as f32) + 0.5);
+                let y = cells_size * ((row as f32) + 0.5);
                 let position = layout.bounds().position() + Vector::new(x, y);
-                let size = Size::new(self.cells_size, self.cells_size);
+                let size = Size::new(cells_size, cells_size);
                 let bounds = Rectangle::new(position, size);
 
                 res.push(Primitive::Quad {
@@ -181,33 +491,88 @@ impl<Message> ChessBoard<Message> {
                     border_radius: 0.0,
                     border_width: 0.0,
                     border_color: Color::TRANSPARENT,
-                })
+                });
+
+                if let Some((last_start, last_end)) = self.state.last_move {
+                    if [file, rank] == last_start || [file, rank] == last_end {
+                        res.push(Primitive::Quad {
+                            bounds,
+                            background: Background::Color(self.theme.last_move),
+                            border_radius: 0.0,
+                            border_width: 0.0,
+                            border_color: Color::TRANSPARENT,
+                        });
+                    }
+                }
             }
         }
 
+        if let Some(primitive) = self.get_check_primitive(layout) {
+            res.push(primitive);
+        }
+
         res
     }
 
+    /// Radial highlight under the king in check, faked with concentric
+    /// rounded quads as quads can't hold gradients.
+    fn get_check_primitive(&self, layout: &Layout<'_>) -> Option<Primitive> {
+        let cells_size = self.get_cells_size(layout);
+        if self.setup.is_some() || !self.rules.is_check() {
+            return None;
+        }
+
+        let [file, rank] = self.rules.king_cell(self.rules.side_to_move())?;
+        let col = if self.reversed { 7 - file } else { file };
+        let row = if self.reversed { rank } else { 7 - rank };
+        let cell_x = cells_size * ((col as f32) + 0.5);
+        let cell_y = cells_size * ((row as f32) + 0.5);
+
+        let steps = 6;
+        let mut primitives: Vec<Primitive> = Vec::new();
+        for step in 0..steps {
+            let ratio = 1.0 - (step as f32) / (steps as f32);
+            let size = cells_size * ratio;
+            let offset = (cells_size - size) * 0.5;
+            let position =
+                layout.bounds().position() + Vector::new(cell_x + offset, cell_y + offset);
+            let color = Color {
+                a: self.theme.check.a * 0.3,
+                ..self.theme.check
+            };
+            primitives.push(Primitive::Quad {
+                bounds: Rectangle::new(position, Size::new(size, size)),
+                background: Background::Color(color),
+                border_radius: size * 0.5,
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            });
+        }
+
+        Some(Primitive::Group { primitives })
+    }
+
     fn get_cells_coordinates_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
         let mut res: Vec<Primitive> = Vec::new();
 
         let upper_a_ordinal = 65u8;
         let digit_1_ordinal = 49u8;
-        let size = self.cells_size * 0.45;
+        let size = cells_size * 0.45;
         let font = Font::Default;
         let horizontal_alignment = HorizontalAlignment::Center;
         let vertical_alignment = VerticalAlignment::Center;
-        let color = Color::from_rgb8(255, 199, 0);
+        let color = self.theme.coordinates;
 
         for col in 0..8 {
             let file = if self.reversed { 7 - col } else { col };
             let content = format!("{}", (upper_a_ordinal + file) as char);
-            let x = self.cells_size * (1.0 + (col as f32));
-            let y1 = self.cells_size * 0.25f32;
-            let y2 = self.cells_size * 8.75f32;
+            let x = cells_size * (1.0 + (col as f32));
+            let y1 = cells_size * 0.25f32;
+            let y2 = cells_size * 8.75f32;
             let position_1 = layout.bounds().position() + Vector::new(x, y1);
             let position_2 = layout.bounds().position() + Vector::new(x, y2);
-            let board_size = Size::new(self.cells_size, self.cells_size);
+            let board_size = Size::new(cells_size, cells_size);
             let bounds_1 = Rectangle::new(position_1, board_size);
             let bounds_2 = Rectangle::new(position_2, board_size);
 
@@ -235,12 +600,12 @@ impl<Message> ChessBoard<Message> {
         for row in 0..8 {
             let rank = if self.reversed { row } else { 7 - row };
             let content = format!("{}", (digit_1_ordinal + rank) as char);
-            let y = self.cells_size * (1.0 + (row as f32));
-            let x1 = self.cells_size * 0.25f32;
-            let x2 = self.cells_size * 8.75f32;
+            let y = cells_size * (1.0 + (row as f32));
+            let x1 = cells_size * 0.25f32;
+            let x2 = cells_size * 8.75f32;
             let position_1 = layout.bounds().position() + Vector::new(x1, y);
             let position_2 = layout.bounds().position() + Vector::new(x2, y);
-            let board_size = Size::new(self.cells_size, self.cells_size);
+            let board_size = Size::new(cells_size, cells_size);
             let bounds_1 = Rectangle::new(position_1, board_size);
             let bounds_2 = Rectangle::new(position_2, board_size);
 
@@ -269,6 +634,7 @@ impl<Message> ChessBoard<Message> {
     }
 
     fn get_pieces_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
         let mut res: Vec<Primitive> = Vec::new();
 
         for row in 0..8 {
@@ -276,27 +642,27 @@ impl<Message> ChessBoard<Message> {
             for col in 0..8 {
                 let file = if self.reversed { 7 - col } else { col };
 
-                if self.dnd_state.active {
-                    if let Some(start_cell) = self.dnd_state.start_cell {
+                if self.state.dnd_state.dragging {
+                    if let Some(start_cell) = self.state.dnd_state.start_cell {
                         if file == start_cell[0] && rank == start_cell[1] {
                             continue;
                         }
                     }
                 }
 
-                let square = SQ(file + 8 * rank);
-                let piece = self.board.piece_at_sq(square);
-                if piece != Piece::None {
-                    let asset_name = asset_name_for_piece(&piece);
-                    let handle = self.piece_assets.clone()[asset_name.as_str()].clone();
+                if self.is_animation_target([file, rank]) || self.is_snap_back_target([file, rank])
+                {
+                    continue;
+                }
 
-                    let x = self.cells_size * ((col as f32) + 0.5);
-                    let y = self.cells_size * ((row as f32) + 0.5);
+                if let Some(piece) = self.piece_at([file, rank]) {
+                    let x = cells_size * ((col as f32) + 0.5);
+                    let y = cells_size * ((row as f32) + 0.5);
                     let position = layout.bounds().position() + Vector::new(x, y);
-                    let size = Size::new(self.cells_size, self.cells_size);
+                    let size = Size::new(cells_size, cells_size);
                     let bounds = Rectangle::new(position, size);
 
-                    res.push(Primitive::Svg { bounds, handle })
+                    res.push(self.piece_set.primitive(piece, bounds))
                 }
             }
         }
@@ -304,15 +670,280 @@ impl<Message> ChessBoard<Message> {
         res
     }
 
+    /// The cell of the piece being dragged, or else of the selected piece.
+    fn get_picked_cell(&self) -> Option<[u8; 2]> {
+        if self.state.dnd_state.dragging {
+            self.state.dnd_state.start_cell
+        } else {
+            self.state.selected_cell
+        }
+    }
+
+    /// Legal destinations of the picked piece, as (file, rank, is_capture).
+    /// Castling is shown on the rook too, where the king can be dropped.
+    fn get_picked_piece_destinations(&self) -> Vec<(u8, u8, bool)> {
+        let start_cell = match self.get_picked_cell() {
+            Some(start_cell) if self.setup.is_none() => start_cell,
+            _ => return Vec::new(),
+        };
+
+        let mut res: Vec<(u8, u8, bool)> = Vec::new();
+        for legal_move in self.rules.legal_moves() {
+            if legal_move.from != start_cell {
+                continue;
+            }
+            let capture = self.rules.is_capture(&legal_move);
+            let mut cells = vec![legal_move.to];
+            cells.extend(legal_move.castling_rook);
+            for [file, rank] in cells {
+                let destination = (file, rank, capture);
+                // Promotions give one move per piece for the same destination.
+                if [file, rank] != start_cell && !res.contains(&destination) {
+                    res.push(destination);
+                }
+            }
+        }
+
+        res
+    }
+
+    fn get_move_hints_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
+        let mut res: Vec<Primitive> = Vec::new();
+
+        if !self.show_move_hints {
+            return res;
+        }
+
+        let color = self.theme.move_hint;
+        for (file, rank, is_capture) in self.get_picked_piece_destinations() {
+            let col = if self.reversed { 7 - file } else { file };
+            let row = if self.reversed { rank } else { 7 - rank };
+            let cell_x = cells_size * ((col as f32) + 0.5);
+            let cell_y = cells_size * ((row as f32) + 0.5);
+
+            if is_capture {
+                let position = layout.bounds().position() + Vector::new(cell_x, cell_y);
+                let size = Size::new(cells_size, cells_size);
+                res.push(Primitive::Quad {
+                    bounds: Rectangle::new(position, size),
+                    background: Background::Color(Color::TRANSPARENT),
+                    border_radius: cells_size * 0.5,
+                    border_width: cells_size * 0.08,
+                    border_color: color,
+                });
+            } else {
+                let dot_size = cells_size * 0.3;
+                let offset = (cells_size - dot_size) * 0.5;
+                let position =
+                    layout.bounds().position() + Vector::new(cell_x + offset, cell_y + offset);
+                let size = Size::new(dot_size, dot_size);
+                res.push(Primitive::Quad {
+                    bounds: Rectangle::new(position, size),
+                    background: Background::Color(color),
+                    border_radius: dot_size * 0.5,
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                });
+            }
+        }
+
+        res
+    }
+
+    fn get_cell_center(&self, cell: [u8; 2], layout: &Layout<'_>) -> Point {
+        let cells_size = self.get_cells_size(layout);
+        let [file, rank] = cell;
+        let col = if self.reversed { 7 - file } else { file };
+        let row = if self.reversed { rank } else { 7 - rank };
+        Point::new(
+            cells_size * ((col as f32) + 1.0),
+            cells_size * ((row as f32) + 1.0),
+        )
+    }
+
+    /// Triangles of an arrow, in coordinates local to the board.
+    fn get_arrow_mesh(
+        &self,
+        from: [u8; 2],
+        to: [u8; 2],
+        color: Color,
+        layout: &Layout<'_>,
+    ) -> Mesh2D {
+        let cells_size = self.get_cells_size(layout);
+        let start = self.get_cell_center(from, layout);
+        let end = self.get_cell_center(to, layout);
+        let length = start.distance(end);
+        let direction = Vector::new((end.x - start.x) / length, (end.y - start.y) / length);
+        let normal = Vector::new(-direction.y, direction.x);
+
+        let shaft_half_width = cells_size * 0.1;
+        let head_half_width = cells_size * 0.25;
+        let head_length = cells_size * 0.4;
+        let head_start = end - direction * head_length;
+
+        let color = color.into_linear();
+        let vertex = |point: Point| Vertex2D {
+            position: [point.x, point.y],
+            color,
+        };
+
+        Mesh2D {
+            vertices: vec![
+                vertex(start + normal * shaft_half_width),
+                vertex(start - normal * shaft_half_width),
+                vertex(head_start - normal * shaft_half_width),
+                vertex(head_start + normal * shaft_half_width),
+                vertex(head_start + normal * head_half_width),
+                vertex(head_start - normal * head_half_width),
+                vertex(end),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3, 4, 5, 6],
+        }
+    }
+
+    fn get_annotations_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
+        let mut res: Vec<Primitive> = Vec::new();
+
+        for annotation in &self.annotations {
+            match *annotation {
+                Annotation::Circle { cell, color } => {
+                    let center = self.get_cell_center(cell, layout);
+                    let position = layout.bounds().position()
+                        + Vector::new(center.x - cells_size * 0.5, center.y - cells_size * 0.5);
+                    let size = Size::new(cells_size, cells_size);
+                    res.push(Primitive::Quad {
+                        bounds: Rectangle::new(position, size),
+                        background: Background::Color(Color::TRANSPARENT),
+                        border_radius: cells_size * 0.5,
+                        border_width: cells_size * 0.08,
+                        border_color: color_for_annotation(color),
+                    });
+                }
+                Annotation::Arrow { from, to, color } => {
+                    if from == to {
+                        continue;
+                    }
+                    let buffers =
+                        self.get_arrow_mesh(from, to, color_for_annotation(color), layout);
+                    res.push(Primitive::Translate {
+                        translation: Vector::new(layout.bounds().x, layout.bounds().y),
+                        content: Box::new(Primitive::Mesh2D {
+                            buffers,
+                            size: layout.bounds().size(),
+                        }),
+                    });
+                }
+            }
+        }
+
+        res
+    }
+
+    fn get_running_animation(&self) -> Option<&PieceAnimation> {
+        self.animation
+            .as_ref()
+            .filter(|animation| !animation.is_finished(Instant::now()))
+    }
+
+    fn is_animation_target(&self, cell: [u8; 2]) -> bool {
+        match self.get_running_animation() {
+            Some(animation) => animation
+                .sliding_pieces()
+                .iter()
+                .any(|sliding_piece| sliding_piece.to == cell),
+            None => false,
+        }
+    }
+
+    fn get_animation_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
+        let mut res: Vec<Primitive> = Vec::new();
+
+        let animation = match self.get_running_animation() {
+            Some(animation) => animation,
+            None => return res,
+        };
+        let progress = animation.progress(Instant::now());
+        let size = Size::new(cells_size, cells_size);
+        let half_cell = Vector::new(cells_size * 0.5, cells_size * 0.5);
+
+        // Image primitives have no opacity, so fading pieces are progressively
+        // covered by their cell color instead.
+        for (piece, cell) in animation.fading_pieces() {
+            let top_left = self.get_cell_center(*cell, layout) - half_cell;
+            let position = layout.bounds().position() + Vector::new(top_left.x, top_left.y);
+            let bounds = Rectangle::new(position, size);
+            res.push(self.piece_set.primitive(*piece, bounds));
+
+            let cover_color = Color {
+                a: progress,
+                ..self.get_cell_color(*cell)
+            };
+            res.push(Primitive::Quad {
+                bounds,
+                background: Background::Color(cover_color),
+                border_radius: 0.0,
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            });
+        }
+
+        for sliding_piece in animation.sliding_pieces() {
+            let from = self.get_cell_center(sliding_piece.from, layout);
+            let to = self.get_cell_center(sliding_piece.to, layout);
+            let current = Point::new(
+                from.x + (to.x - from.x) * progress,
+                from.y + (to.y - from.y) * progress,
+            );
+            let top_left = current - half_cell;
+            let position = layout.bounds().position() + Vector::new(top_left.x, top_left.y);
+            let bounds = Rectangle::new(position, size);
+            res.push(self.piece_set.primitive(sliding_piece.piece, bounds));
+        }
+
+        res
+    }
+
+    fn get_running_snap_back(&self) -> Option<&SnapBack> {
+        self.state
+            .snap_back
+            .as_ref()
+            .filter(|snap_back| !snap_back.is_finished(Instant::now()))
+    }
+
+    fn is_snap_back_target(&self, cell: [u8; 2]) -> bool {
+        self.get_running_snap_back()
+            .is_some_and(|snap_back| snap_back.cell == cell)
+    }
+
+    fn get_snap_back_primitive(&self, layout: &Layout<'_>) -> Option<Primitive> {
+        let snap_back = self.get_running_snap_back()?;
+        let cells_size = self.get_cells_size(layout);
+        let half_cell = Vector::new(cells_size * 0.5, cells_size * 0.5);
+        let from = snap_back.offset;
+        let to = self.get_cell_center(snap_back.cell, layout) - half_cell;
+        let progress = snap_back.progress(Instant::now());
+        let current = Vector::new(
+            from.x + (to.x - from.x) * progress,
+            from.y + (to.y - from.y) * progress,
+        );
+        let position = layout.bounds().position() + current;
+        let bounds = Rectangle::new(position, Size::new(cells_size, cells_size));
+        Some(self.piece_set.primitive(snap_back.piece, bounds))
+    }
+
     fn get_player_turn_primitive(&self, layout: &Layout<'_>) -> Primitive {
-        let x = self.cells_size * 8.55;
-        let y = self.cells_size * 8.55;
-        let border_radius = self.cells_size * 0.4;
+        let cells_size = self.get_cells_size(layout);
+        let x = cells_size * 8.55;
+        let y = cells_size * 8.55;
+        let border_radius = cells_size * 0.4;
         let position = layout.bounds().position() + Vector::new(x, y);
         let size = Size::new(border_radius, border_radius);
         let bounds = Rectangle::new(position, size);
 
-        let white_turn = self.board.turn() == Player::White;
+        let white_turn = self.is_white_turn();
         let background = Background::Color(if white_turn {
             Color::WHITE
         } else {
@@ -328,17 +959,18 @@ impl<Message> ChessBoard<Message> {
         }
     }
 
-    fn get_move_piece_primitive(&self) -> Option<Primitive> {
-        if let Some(moved_piece) = self.dnd_state.moved_piece {
-            if let Some([x, y]) = self.dnd_state.moved_piece_location {
-                let asset_name = asset_name_for_piece(&moved_piece);
-                let handle = self.piece_assets.clone()[asset_name.as_str()].clone();
-
+    fn get_move_piece_primitive(&self, layout: &Layout<'_>) -> Option<Primitive> {
+        let cells_size = self.get_cells_size(layout);
+        if !self.state.dnd_state.dragging {
+            return None;
+        }
+        if let Some(moved_piece) = self.state.dnd_state.moved_piece {
+            if let Some([x, y]) = self.state.dnd_state.moved_piece_location {
                 let position = Point::new(x, y);
-                let size = Size::new(self.cells_size, self.cells_size);
+                let size = Size::new(cells_size, cells_size);
                 let bounds = Rectangle::new(position, size);
 
-                Some(Primitive::Svg { bounds, handle })
+                Some(self.piece_set.primitive(moved_piece, bounds))
             } else {
                 None
             }
@@ -347,139 +979,475 @@ impl<Message> ChessBoard<Message> {
         }
     }
 
-    fn handle_mouse_move(&mut self, x: f32, y: f32, layout: &Layout<'_>) {
+    /// Screen cells (column, row) covered by the promotion overlay, in the
+    /// same order as `PROMOTION_PIECES`.
+    fn get_promotion_cells(&self, promotion_state: &PromotionState) -> Vec<[u8; 2]> {
+        let [file, rank] = promotion_state.end_cell;
+        let col = if self.reversed { 7 - file } else { file };
+        let row = if self.reversed { rank } else { 7 - rank };
+
+        (0..4u8)
+            .map(|index| {
+                if row == 0 {
+                    [col, index]
+                } else {
+                    [col, 7 - index]
+                }
+            })
+            .collect()
+    }
+
+    fn get_promotion_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let cells_size = self.get_cells_size(layout);
+        let mut res: Vec<Primitive> = Vec::new();
+
+        if let Some(ref promotion_state) = self.state.promotion_state {
+            let board_position =
+                layout.bounds().position() + Vector::new(cells_size * 0.5, cells_size * 0.5);
+            let board_size = Size::new(cells_size * 8.0, cells_size * 8.0);
+            res.push(Primitive::Quad {
+                bounds: Rectangle::new(board_position, board_size),
+                background: Background::Color(Color::from_rgba8(0, 0, 0, 0.5)),
+                border_radius: 0.0,
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            });
+
+            let cells = self.get_promotion_cells(promotion_state);
+            for (piece_kind, [col, row]) in PROMOTION_PIECES.iter().zip(cells) {
+                let x = cells_size * ((col as f32) + 0.5);
+                let y = cells_size * ((row as f32) + 0.5);
+                let position = layout.bounds().position() + Vector::new(x, y);
+                let size = Size::new(cells_size, cells_size);
+                let bounds = Rectangle::new(position, size);
+
+                res.push(Primitive::Quad {
+                    bounds,
+                    background: Background::Color(self.theme.light_cell),
+                    border_radius: cells_size * 0.5,
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                });
+
+                let piece = Piece::new(promotion_state.side, *piece_kind);
+                res.push(self.piece_set.primitive(piece, bounds));
+            }
+        }
+
+        res
+    }
+
+    /// Completes the pending promotion if the click is on one of the overlay
+    /// pieces, and cancels it otherwise.
+    fn handle_promotion_click(&mut self, x: f32, y: f32, layout: &Layout<'_>) -> bool {
+        let cells_size = self.get_cells_size(layout);
+        let promotion_state = match self.state.promotion_state.take() {
+            Some(promotion_state) => promotion_state,
+            None => return false,
+        };
+
         let self_bounds = layout.bounds();
         let local_x = x - self_bounds.x;
         let local_y = y - self_bounds.y;
-        let col = ((local_x - self.cells_size * 0.5) / self.cells_size).floor() as i32;
-        let row = ((local_y - self.cells_size * 0.5) / self.cells_size).floor() as i32;
+        let col = ((local_x - cells_size * 0.5) / cells_size).floor() as i32;
+        let row = ((local_y - cells_size * 0.5) / cells_size).floor() as i32;
+        if !(0..=7).contains(&col) || !(0..=7).contains(&row) {
+            return false;
+        }
+
+        let cells = self.get_promotion_cells(&promotion_state);
+        let chosen_piece = PROMOTION_PIECES
+            .iter()
+            .zip(cells)
+            .find(|(_, cell)| *cell == [col as u8, row as u8])
+            .map(|(piece_kind, _)| *piece_kind);
+
+        match chosen_piece {
+            Some(piece_kind) => {
+                let chess_move = Move {
+                    promotion: Some(piece_kind),
+                    ..Move::new(promotion_state.start_cell, promotion_state.end_cell)
+                };
+                let success = self.rules.play(&chess_move);
+                if success {
+                    self.state.last_move =
+                        Some((promotion_state.start_cell, promotion_state.end_cell));
+                }
+                success
+            }
+            None => false,
+        }
+    }
+
+    /// The (file, rank) cell under the given window coordinates, if any.
+    fn get_cell_at(&self, x: f32, y: f32, layout: &Layout<'_>) -> Option<[u8; 2]> {
+        let cells_size = self.get_cells_size(layout);
+        let self_bounds = layout.bounds();
+        let local_x = x - self_bounds.x;
+        let local_y = y - self_bounds.y;
+        let col = ((local_x - cells_size * 0.5) / cells_size).floor() as i32;
+        let row = ((local_y - cells_size * 0.5) / cells_size).floor() as i32;
+        if !(0..=7).contains(&col) || !(0..=7).contains(&row) {
+            return None;
+        }
         let file = if self.reversed { 7 - col } else { col };
         let rank = if self.reversed { row } else { 7 - row };
 
-        let out_of_bounds = col < 0 || col > 7 || row < 0 || row > 7;
-        if self.dnd_state.active {
-            if self.dnd_state.start_cell.is_none() {
-                if out_of_bounds {
-                    self.dnd_state.active = false;
-                } else {
-                    let cell_has_player_in_turn_piece =
-                        self.cell_has_player_in_turn_piece(file as u8, rank as u8);
-                    if cell_has_player_in_turn_piece {
-                        self.dnd_state.start_cell = Some([file as u8, rank as u8]);
-                        self.dnd_state.moved_piece =
-                            Some(self.board.piece_at_sq(SQ(file as u8 + 8 * rank as u8)));
-                    } else {
-                        self.dnd_state.active = false;
-                    }
+        Some([file as u8, rank as u8])
+    }
+
+    /// Either plays the selected piece to the pressed cell, or picks up the
+    /// pressed piece, which becomes a drag or a selection on release.
+    fn handle_mouse_press(&mut self, x: f32, y: f32, layout: &Layout<'_>) -> DropOutcome {
+        let cell = match self.get_cell_at(x, y, layout) {
+            Some(cell) => cell,
+            None => {
+                self.state.selected_cell = None;
+                return DropOutcome::Ignored;
+            }
+        };
+
+        if let Some(selected_cell) = self.state.selected_cell {
+            let reselecting = selected_cell == cell || self.cell_has_player_in_turn_piece(cell);
+            if !reselecting {
+                self.state.selected_cell = None;
+                return self.try_move(selected_cell, cell);
+            }
+        }
+
+        // Opponent pieces can be picked up too, so that moving them
+        // is reported as an illegal move instead of being ignored.
+        match self.rules.piece_at(cell) {
+            Some(piece) => {
+                self.state.dnd_state.active = true;
+                self.state.dnd_state.press_location = Some([x, y]);
+                self.state.dnd_state.press_time = Some(Instant::now());
+                self.state.dnd_state.start_cell = Some(cell);
+                self.state.dnd_state.moved_piece = Some(piece);
+            }
+            None => self.state.selected_cell = None,
+        }
+
+        DropOutcome::Ignored
+    }
+
+    fn handle_mouse_move(&mut self, x: f32, y: f32, layout: &Layout<'_>) {
+        let cells_size = self.get_cells_size(layout);
+        if !self.state.dnd_state.active {
+            return;
+        }
+
+        if !self.state.dnd_state.dragging {
+            if !self.may_move_picked_piece() {
+                return;
+            }
+            if let Some([press_x, press_y]) = self.state.dnd_state.press_location {
+                let distance = ((x - press_x).powi(2) + (y - press_y).powi(2)).sqrt();
+                if distance >= DRAG_THRESHOLD {
+                    self.state.dnd_state.dragging = true;
+                    self.state.selected_cell = None;
                 }
             }
-            self.dnd_state.moved_piece_location =
-                Some([x - self.cells_size * 0.5f32, y - self.cells_size * 0.5f32]);
         }
-        // We do need a different test starting the same way as the previous one.
-        // Because of the special case where dnd_state.active has been reset to false there.
-        if self.dnd_state.active {
-            self.dnd_state.end_cell = if out_of_bounds {
+
+        if self.state.dnd_state.dragging {
+            self.state.dnd_state.moved_piece_location =
+                Some([x - cells_size * 0.5f32, y - cells_size * 0.5f32]);
+            self.state.dnd_state.end_cell = self.get_cell_at(x, y, layout);
+        }
+    }
+
+    fn handle_mouse_release(&mut self, layout: &Layout<'_>) -> DropOutcome {
+        if !self.state.dnd_state.active {
+            return DropOutcome::Ignored;
+        }
+        if self.state.dnd_state.dragging && self.state.dnd_state.end_cell.is_none() {
+            return self.cancel_drag(layout);
+        }
+
+        let may_move = self.may_move_picked_piece();
+        let dragging = self.state.dnd_state.dragging;
+        let start_cell = self.state.dnd_state.start_cell;
+        let end_cell = self.state.dnd_state.end_cell;
+        self.state.dnd_state.reset();
+
+        if !may_move {
+            self.state.selected_cell = None;
+            return DropOutcome::Ignored;
+        }
+
+        if !dragging {
+            // A short click: toggles the selection of the clicked piece.
+            self.state.selected_cell = if self.state.selected_cell == start_cell {
                 None
             } else {
-                Some([file as u8, rank as u8])
+                start_cell
             };
+            return DropOutcome::Ignored;
+        }
+
+        match (start_cell, end_cell) {
+            (Some(start_cell), Some(end_cell)) if start_cell != end_cell => {
+                self.try_move(start_cell, end_cell)
+            }
+            _ => DropOutcome::Ignored,
+        }
+    }
+
+    /// Gives up the current drag, sliding the piece back to its cell. A piece
+    /// only pressed, not dragged yet, is simply put down.
+    fn cancel_drag(&mut self, layout: &Layout<'_>) -> DropOutcome {
+        let dnd_state = &self.state.dnd_state;
+        let snap_back = match (dnd_state.moved_piece, dnd_state.start_cell) {
+            (Some(piece), Some(cell)) if dnd_state.dragging => {
+                dnd_state.moved_piece_location.map(|[x, y]| SnapBack {
+                    piece,
+                    cell,
+                    offset: Point::new(x, y) - layout.bounds().position(),
+                    start: Instant::now(),
+                })
+            }
+            _ => None,
+        };
+        let held = dnd_state
+            .press_time
+            .map(|press_time| press_time.elapsed())
+            .unwrap_or_default();
+        self.state.dnd_state.reset();
+
+        match snap_back {
+            Some(snap_back) => {
+                let outcome = DropOutcome::Cancelled(DragCancelled {
+                    from: cell_name(snap_back.cell),
+                    held,
+                });
+                self.state.snap_back = Some(snap_back);
+                outcome
+            }
+            None => DropOutcome::Ignored,
+        }
+    }
+
+    /// The legal move of a piece dropped from one cell on the other. A king
+    /// dropped on the destination of a castling also moves normally there
+    /// when it can.
+    fn find_legal_move(&self, start_cell: [u8; 2], end_cell: [u8; 2]) -> Option<Move> {
+        let legal_moves = self.rules.legal_moves();
+        legal_moves
+            .iter()
+            .find(|legal_move| {
+                !legal_move.is_castling() && legal_move.is_drop(start_cell, end_cell)
+            })
+            .or_else(|| {
+                legal_moves
+                    .iter()
+                    .find(|legal_move| legal_move.is_drop(start_cell, end_cell))
+            })
+            .copied()
+    }
+
+    /// Plays the move, or opens the promotion overlay when a piece must be
+    /// chosen first.
+    fn try_move(&mut self, start_cell: [u8; 2], end_cell: [u8; 2]) -> DropOutcome {
+        let chess_move = match self.find_legal_move(start_cell, end_cell) {
+            Some(chess_move) => chess_move,
+            None => {
+                return DropOutcome::Illegal(IllegalMove {
+                    from: cell_name(start_cell),
+                    to: cell_name(end_cell),
+                    reason: self.illegal_move_reason(start_cell, end_cell),
+                })
+            }
+        };
+
+        if chess_move.promotion.is_some() {
+            self.state.promotion_state = Some(PromotionState {
+                start_cell,
+                end_cell,
+                side: self.rules.side_to_move(),
+            });
+            return DropOutcome::Ignored;
+        }
+
+        if self.rules.play(&chess_move) {
+            self.state.last_move = Some((start_cell, end_cell));
+            DropOutcome::Moved
         } else {
-            self.dnd_state.end_cell = None;
+            DropOutcome::Ignored
         }
     }
 
-    fn handle_mouse_release(&mut self) -> bool {
-        if !self.dnd_state.active {
-            false
+    fn handle_right_press(&mut self, x: f32, y: f32, layout: &Layout<'_>) {
+        self.state.annotation_start = self.get_cell_at(x, y, layout);
+    }
+
+    /// Right click circles a cell, right drag draws an arrow; drawing the
+    /// same annotation again removes it.
+    fn handle_right_release(&mut self, x: f32, y: f32, layout: &Layout<'_>) -> bool {
+        let start_cell = match self.state.annotation_start.take() {
+            Some(start_cell) => start_cell,
+            None => return false,
+        };
+        let end_cell = match self.get_cell_at(x, y, layout) {
+            Some(end_cell) => end_cell,
+            None => return false,
+        };
+
+        let color = annotation_color_for_modifiers(self.state.modifiers);
+        let new_annotation = if start_cell == end_cell {
+            Annotation::Circle {
+                cell: start_cell,
+                color,
+            }
         } else {
-            if let Some([end_file, end_rank]) = self.dnd_state.end_cell {
-                let out_of_bounds = end_file > 7 || end_rank > 7;
-                if out_of_bounds {
-                    self.dnd_state.reset();
-                    false
-                } else {
-                    if let Some([start_file, start_rank]) = self.dnd_state.start_cell {
-                        let ascii_lower_a = 97u8;
-                        let ascii_digit_1 = 49u8;
-
-                        let start_cell_uci = format!(
-                            "{}{}",
-                            (ascii_lower_a + start_file) as char,
-                            (ascii_digit_1 + start_rank) as char,
-                        );
-                        let end_cell_uci = format!(
-                            "{}{}",
-                            (ascii_lower_a + end_file) as char,
-                            (ascii_digit_1 + end_rank) as char
-                        );
-                        let move_uci = format!("{}{}", start_cell_uci, end_cell_uci);
-
-                        self.board.apply_uci_move(&move_uci);
-                        self.dnd_state.reset();
-
-                        true
-                    } else {
-                        false
-                    }
+            Annotation::Arrow {
+                from: start_cell,
+                to: end_cell,
+                color,
+            }
+        };
+        annotation::toggle(&mut self.annotations, new_annotation);
+
+        true
+    }
+
+    fn publish_outcome(&self, outcome: DropOutcome, messages: &mut Vec<Message>) {
+        match outcome {
+            DropOutcome::Moved => {
+                let new_position_fen = self.rules.fen();
+                if let Some(ref message) = self.on_position_changed {
+                    let message = message(new_position_fen);
+                    messages.push(message);
                 }
-            } else {
-                self.dnd_state.reset();
-                false
-            }
-        }
-    }
-
-    fn cell_has_player_in_turn_piece(&self, file: u8, rank: u8) -> bool {
-        let piece_at_square = self.board.piece_at_sq(SQ(file + 8 * rank));
-        let white_turn = self.board.turn() == Player::White;
-        let white_pieces = vec![
-            Piece::WhitePawn,
-            Piece::WhiteKnight,
-            Piece::WhiteBishop,
-            Piece::WhiteRook,
-            Piece::WhiteQueen,
-            Piece::WhiteKing,
-        ];
-        let black_pieces = vec![
-            Piece::BlackPawn,
-            Piece::BlackKnight,
-            Piece::BlackBishop,
-            Piece::BlackRook,
-            Piece::BlackQueen,
-            Piece::BlackKing,
-        ];
-
-        if white_turn {
-            white_pieces.contains(&piece_at_square)
+            }
+            DropOutcome::Illegal(illegal_move) => {
+                if let Some(ref message) = self.on_illegal_move {
+                    let message = message(illegal_move);
+                    messages.push(message);
+                }
+            }
+            DropOutcome::Cancelled(drag_cancelled) => {
+                if let Some(ref message) = self.on_drag_cancelled {
+                    let message = message(drag_cancelled);
+                    messages.push(message);
+                }
+            }
+            DropOutcome::Ignored => {}
+        }
+    }
+
+    /// Tells a move the piece can't make apart from one leaving the king in
+    /// check.
+    fn illegal_move_reason(&self, start_cell: [u8; 2], end_cell: [u8; 2]) -> IllegalMoveReason {
+        if !self.cell_has_player_in_turn_piece(start_cell) {
+            return IllegalMoveReason::NotYourPiece;
+        }
+
+        let is_move_of = |chess_move: &Move| chess_move.is_drop(start_cell, end_cell);
+        let king = Piece::new(self.rules.side_to_move(), PieceKind::King);
+        let rook = Piece::new(self.rules.side_to_move(), PieceKind::Rook);
+        if self.rules.pseudo_legal_moves().iter().any(is_move_of) {
+            IllegalMoveReason::KingWouldBeInCheck
+        } else if self.rules.piece_at(start_cell) == Some(king)
+            && self.rules.piece_at(end_cell) == Some(rook)
+        {
+            IllegalMoveReason::CastlingNotAllowed
         } else {
-            black_pieces.contains(&piece_at_square)
+            IllegalMoveReason::PieceCannotMoveThere
         }
     }
+
+    /// Whether the interaction mode lets the user move the pressed piece.
+    fn may_move_picked_piece(&self) -> bool {
+        self.state
+            .dnd_state
+            .moved_piece
+            .is_some_and(|piece| self.interaction_mode.allows(piece.side()))
+    }
+
+    /// A grabbing hand while a piece is held, an open one over the pieces
+    /// which can be picked up.
+    fn get_mouse_interaction(&self, cursor: Point, layout: &Layout<'_>) -> mouse::Interaction {
+        let holding =
+            self.state.dnd_state.active && (self.setup.is_some() || self.may_move_picked_piece());
+        if holding {
+            return mouse::Interaction::Grabbing;
+        }
+        if !layout.bounds().contains(cursor) {
+            return mouse::Interaction::Idle;
+        }
+        if self.state.promotion_state.is_some() {
+            return mouse::Interaction::Pointer;
+        }
+
+        let piece = self
+            .get_cell_at(cursor.x, cursor.y, layout)
+            .and_then(|cell| self.piece_at(cell));
+        let grabbable = match self.setup {
+            Some(_) => {
+                piece.is_some()
+                    || self
+                        .get_palette_piece_at(cursor.x, cursor.y, layout)
+                        .is_some()
+            }
+            None => piece.is_some_and(|piece| self.interaction_mode.allows(piece.side())),
+        };
+        if grabbable {
+            mouse::Interaction::Grab
+        } else {
+            mouse::Interaction::Idle
+        }
+    }
+
+    fn cell_has_player_in_turn_piece(&self, cell: [u8; 2]) -> bool {
+        self.rules
+            .piece_at(cell)
+            .is_some_and(|piece| piece.side() == self.rules.side_to_move())
+    }
 }
 
-impl<Message, B> Widget<Message, Renderer<B>> for ChessBoard<Message>
+impl<'a, Message, B> Widget<Message, Renderer<B>> for ChessBoard<'a, Message>
 where
     Message: Clone,
     B: Backend,
 {
     fn width(&self) -> Length {
-        Length::Shrink
+        Length::Fill
     }
 
     fn height(&self) -> Length {
-        Length::Shrink
+        Length::Fill
     }
 
-    fn layout(&self, _renderer: &Renderer<B>, _limits: &layout::Limits) -> layout::Node {
-        let total_size = self.cells_size * 9.0;
-        layout::Node::new(Size::new(total_size, total_size))
+    /// Takes the largest square fitting in the limits, clamped between the
+    /// minimum and maximum sizes. The setup editor adds its palette under the
+    /// square.
+    fn layout(&self, _renderer: &Renderer<B>, limits: &layout::Limits) -> layout::Node {
+        let rows = if self.setup.is_some() {
+            9.0 + setup_editor::PALETTE_ROWS
+        } else {
+            9.0
+        };
+        let limits = limits.width(Length::Fill).height(Length::Fill);
+        let available = limits.max().width.min(limits.max().height * 9.0 / rows);
+        let size = if available.is_finite() {
+            available
+        } else {
+            self.min_size
+        };
+        let size = size.max(self.min_size).min(self.max_size);
+        layout::Node::new(Size::new(size, size * rows / 9.0))
     }
 
     fn hash_layout(&self, state: &mut Hasher) {
         use std::hash::Hash;
 
-        self.cells_size.to_bits().hash(state);
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.min_size.to_bits().hash(state);
+        self.max_size.to_bits().hash(state);
+        self.setup.is_some().hash(state);
     }
 
     fn draw(
@@ -487,7 +1455,7 @@ where
         _renderer: &mut Renderer<B>,
         _defaults: &Defaults,
         layout: Layout<'_>,
-        _cursor_position: Point,
+        cursor_position: Point,
         _viewport: &Rectangle,
     ) -> (Primitive, mouse::Interaction) {
         let mut res: Vec<Primitive> = Vec::new();
@@ -506,15 +1474,39 @@ where
             res.push(primitive);
         }
 
+        for primitive in self.get_animation_primitives(&layout) {
+            res.push(primitive);
+        }
+
+        if let Some(primitive) = self.get_snap_back_primitive(&layout) {
+            res.push(primitive);
+        }
+
+        for primitive in self.get_palette_primitives(&layout) {
+            res.push(primitive);
+        }
+
+        for primitive in self.get_move_hints_primitives(&layout) {
+            res.push(primitive);
+        }
+
+        for primitive in self.get_annotations_primitives(&layout) {
+            res.push(primitive);
+        }
+
         res.push(self.get_player_turn_primitive(&layout));
 
-        if let Some(primitive) = self.get_move_piece_primitive() {
+        if let Some(primitive) = self.get_move_piece_primitive(&layout) {
+            res.push(primitive);
+        }
+
+        for primitive in self.get_promotion_primitives(&layout) {
             res.push(primitive);
         }
 
         (
             Primitive::Group { primitives: res },
-            mouse::Interaction::default(),
+            self.get_mouse_interaction(cursor_position, &layout),
         )
     }
 
@@ -522,42 +1514,125 @@ where
         &mut self,
         event: Event,
         layout: Layout<'_>,
-        _cursor_position: Point,
+        cursor_position: Point,
         messages: &mut Vec<Message>,
         _renderer: &Renderer<B>,
         _clipboard: Option<&dyn Clipboard>,
     ) -> Status {
+        // The events of a drag belong to the board wherever they happen, so
+        // that releasing off the board still ends the drag.
+        let over_board = layout.bounds().contains(cursor_position);
+        let owned = over_board || self.state.dnd_state.active;
+
         match event {
-            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Left)) => {
-                self.dnd_state.active = true;
-                Status::Captured
-            }
-            Event::Mouse(MouseEvent::ButtonReleased(MouseButton::Left)) => {
-                let success = self.handle_mouse_release();
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Left))
+                if self.state.promotion_state.is_some() =>
+            {
+                // Clicking elsewhere dismisses the promotion overlay.
+                let success =
+                    self.handle_promotion_click(cursor_position.x, cursor_position.y, &layout);
                 if success {
-                    let new_position_fen = self.board.fen();
+                    let new_position_fen = self.rules.fen();
                     if let Some(ref message) = self.on_position_changed {
                         let message = message(new_position_fen);
                         messages.push(message);
                     }
                 }
+                status_of(over_board)
+            }
+            Event::Keyboard(KeyboardEvent::KeyPressed {
+                key_code: KeyCode::Escape,
+                ..
+            }) if self.state.promotion_state.is_some() => {
+                self.state.promotion_state = None;
                 Status::Captured
             }
+            Event::Keyboard(KeyboardEvent::KeyPressed {
+                key_code: KeyCode::Escape,
+                ..
+            }) if self.state.dnd_state.active => {
+                let outcome = self.cancel_drag(&layout);
+                self.publish_outcome(outcome, messages);
+                Status::Captured
+            }
+            Event::Mouse(MouseEvent::CursorLeft) if self.state.dnd_state.active => {
+                let outcome = self.cancel_drag(&layout);
+                self.publish_outcome(outcome, messages);
+                Status::Ignored
+            }
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Left)) if !over_board => {
+                self.state.selected_cell = None;
+                Status::Ignored
+            }
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Left)) if self.setup.is_some() => {
+                self.handle_setup_press(cursor_position.x, cursor_position.y, &layout);
+                Status::Captured
+            }
+            Event::Mouse(MouseEvent::ButtonReleased(MouseButton::Left)) if self.setup.is_some() => {
+                if self.handle_setup_release() {
+                    self.publish_setup(messages);
+                }
+                status_of(owned)
+            }
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Right)) if self.setup.is_some() => {
+                if self.handle_setup_clear(cursor_position.x, cursor_position.y, &layout) {
+                    self.publish_setup(messages);
+                }
+                status_of(over_board)
+            }
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Left)) => {
+                let outcome =
+                    self.handle_mouse_press(cursor_position.x, cursor_position.y, &layout);
+                self.publish_outcome(outcome, messages);
+                Status::Captured
+            }
+            Event::Mouse(MouseEvent::ButtonReleased(MouseButton::Left)) => {
+                let outcome = self.handle_mouse_release(&layout);
+                self.publish_outcome(outcome, messages);
+                status_of(owned)
+            }
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Right))
+                if self.state.dnd_state.active =>
+            {
+                let outcome = self.cancel_drag(&layout);
+                self.publish_outcome(outcome, messages);
+                Status::Captured
+            }
+            Event::Mouse(MouseEvent::ButtonPressed(MouseButton::Right)) => {
+                self.handle_right_press(cursor_position.x, cursor_position.y, &layout);
+                status_of(over_board)
+            }
+            Event::Mouse(MouseEvent::ButtonReleased(MouseButton::Right)) => {
+                let drawing = self.state.annotation_start.is_some();
+                let changed =
+                    self.handle_right_release(cursor_position.x, cursor_position.y, &layout);
+                if changed {
+                    if let Some(ref message) = self.on_annotations_changed {
+                        let message = message(self.annotations.clone());
+                        messages.push(message);
+                    }
+                }
+                status_of(drawing)
+            }
+            Event::Keyboard(KeyboardEvent::ModifiersChanged(modifiers)) => {
+                self.state.modifiers = modifiers;
+                Status::Ignored
+            }
             Event::Mouse(MouseEvent::CursorMoved { x, y }) => {
                 self.handle_mouse_move(x, y, &layout);
-                Status::Captured
+                status_of(owned)
             }
             _ => Status::Ignored,
         }
     }
 }
 
-impl<'a, Message, B> Into<Element<'a, Message, Renderer<B>>> for ChessBoard<Message>
+impl<'a, Message, B> From<ChessBoard<'a, Message>> for Element<'a, Message, Renderer<B>>
 where
     Message: 'a + Clone,
     B: Backend,
 {
-    fn into(self) -> Element<'a, Message, Renderer<B>> {
-        Element::new(self)
+    fn from(chess_board: ChessBoard<'a, Message>) -> Element<'a, Message, Renderer<B>> {
+        Element::new(chess_board)
     }
 }
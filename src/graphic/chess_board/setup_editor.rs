@@ -0,0 +1,172 @@
+use iced_graphics::Primitive;
+use iced_native::{Background, Color, Layout, Point, Rectangle, Size, Vector};
+
+use super::ChessBoard;
+use crate::rules::Piece;
+
+/// Rows of the palette, in cells, under the board frame.
+pub const PALETTE_ROWS: f32 = 2.0;
+
+const WHITE_PALETTE: [Piece; 6] = [
+    Piece::WhiteKing,
+    Piece::WhiteQueen,
+    Piece::WhiteRook,
+    Piece::WhiteBishop,
+    Piece::WhiteKnight,
+    Piece::WhitePawn,
+];
+
+const BLACK_PALETTE: [Piece; 6] = [
+    Piece::BlackKing,
+    Piece::BlackQueen,
+    Piece::BlackRook,
+    Piece::BlackBishop,
+    Piece::BlackKnight,
+    Piece::BlackPawn,
+];
+
+/// Left of the first palette piece, in cells, so that the palette is centered.
+const PALETTE_LEFT: f32 = 1.5;
+
+impl<'a, Message> ChessBoard<'a, Message> {
+    /// The palette rows, the one of the side at the bottom of the board
+    /// first.
+    fn get_palette_rows(&self) -> [[Piece; 6]; 2] {
+        if self.reversed {
+            [BLACK_PALETTE, WHITE_PALETTE]
+        } else {
+            [WHITE_PALETTE, BLACK_PALETTE]
+        }
+    }
+
+    fn get_palette_cell_bounds(&self, row: usize, col: usize, layout: &Layout<'_>) -> Rectangle {
+        let cells_size = self.get_cells_size(layout);
+        let x = cells_size * (PALETTE_LEFT + col as f32);
+        let y = cells_size * (9.0 + row as f32);
+        let position = layout.bounds().position() + Vector::new(x, y);
+        Rectangle::new(position, Size::new(cells_size, cells_size))
+    }
+
+    pub(super) fn get_palette_primitives(&self, layout: &Layout<'_>) -> Vec<Primitive> {
+        let mut res: Vec<Primitive> = Vec::new();
+        if self.setup.is_none() {
+            return res;
+        }
+
+        for (row, pieces) in self.get_palette_rows().iter().enumerate() {
+            for (col, piece) in pieces.iter().enumerate() {
+                let bounds = self.get_palette_cell_bounds(row, col, layout);
+                let color = self.get_cell_color([col as u8, row as u8]);
+                res.push(Primitive::Quad {
+                    bounds,
+                    background: Background::Color(color),
+                    border_radius: 0.0,
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                });
+                res.push(self.piece_set.primitive(*piece, bounds));
+            }
+        }
+
+        res
+    }
+
+    pub(super) fn get_palette_piece_at(
+        &self,
+        x: f32,
+        y: f32,
+        layout: &Layout<'_>,
+    ) -> Option<Piece> {
+        let point = Point::new(x, y);
+        for (row, pieces) in self.get_palette_rows().iter().enumerate() {
+            for (col, piece) in pieces.iter().enumerate() {
+                if self
+                    .get_palette_cell_bounds(row, col, layout)
+                    .contains(point)
+                {
+                    return Some(*piece);
+                }
+            }
+        }
+        None
+    }
+
+    /// Picks up a piece of the palette, which is never used up, or a piece
+    /// of the board.
+    pub(super) fn handle_setup_press(&mut self, x: f32, y: f32, layout: &Layout<'_>) {
+        let (start_cell, piece) = match self.get_palette_piece_at(x, y, layout) {
+            Some(piece) => (None, piece),
+            None => match self.get_cell_at(x, y, layout) {
+                Some(cell) => match self.piece_at(cell) {
+                    Some(piece) => (Some(cell), piece),
+                    None => return,
+                },
+                None => return,
+            },
+        };
+
+        self.state.dnd_state.active = true;
+        self.state.dnd_state.press_location = Some([x, y]);
+        self.state.dnd_state.start_cell = start_cell;
+        self.state.dnd_state.moved_piece = Some(piece);
+    }
+
+    /// Drops the dragged piece, replacing the piece of the target cell. A
+    /// board piece dropped outside of the board is removed. Tells whether
+    /// the setup changed.
+    pub(super) fn handle_setup_release(&mut self) -> bool {
+        if !self.state.dnd_state.active {
+            return false;
+        }
+
+        let dragging = self.state.dnd_state.dragging;
+        let start_cell = self.state.dnd_state.start_cell;
+        let end_cell = self.state.dnd_state.end_cell;
+        let moved_piece = self.state.dnd_state.moved_piece;
+        self.state.dnd_state.reset();
+
+        let (setup, piece) = match (self.setup.as_mut(), moved_piece) {
+            (Some(setup), Some(piece)) if dragging => (setup, piece),
+            _ => return false,
+        };
+        if start_cell.is_some() && start_cell == end_cell {
+            return false;
+        }
+
+        if let Some(start_cell) = start_cell {
+            setup.set_piece(start_cell, None);
+        }
+        if let Some(end_cell) = end_cell {
+            setup.set_piece(end_cell, Some(piece));
+        }
+        start_cell.is_some() || end_cell.is_some()
+    }
+
+    /// Empties the clicked cell. Tells whether the setup changed.
+    pub(super) fn handle_setup_clear(&mut self, x: f32, y: f32, layout: &Layout<'_>) -> bool {
+        let cell = self.get_cell_at(x, y, layout);
+        match (self.setup.as_mut(), cell) {
+            (Some(setup), Some(cell)) if setup.piece_at(cell).is_some() => {
+                setup.set_piece(cell, None);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(super) fn publish_setup(&self, messages: &mut Vec<Message>) {
+        let setup = match self.setup {
+            Some(ref setup) => setup,
+            None => return,
+        };
+
+        if let Some(ref message) = self.on_setup_changed {
+            messages.push(message(setup.clone()));
+        }
+        if let Ok(fen) = setup.validate() {
+            if let Some(ref message) = self.on_position_changed {
+                messages.push(message(fen));
+            }
+        }
+    }
+}
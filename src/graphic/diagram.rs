@@ -0,0 +1,378 @@
+//! Standalone SVG pictures of positions, for printed worksheets. Nothing
+//! here needs a window or a GPU.
+
+use iced_native::{Color, Point, Vector};
+
+use super::board_theme::BoardTheme;
+use super::chess_board::color_for_annotation;
+use super::piece_set::piece_for_file_stem;
+use super::resources;
+use crate::annotation::Annotation;
+use crate::position_setup::PositionSetup;
+use crate::rules::{Piece, Side};
+
+use std::fmt::Write;
+
+/// Size of a cell, in SVG units, matching the merida pieces.
+const CELLS_SIZE: f32 = 45.0;
+
+#[derive(Debug, Clone)]
+pub struct DiagramOptions {
+    /// Black at the bottom.
+    pub reversed: bool,
+    /// Draws the frame with the files and ranks names.
+    pub coordinates: bool,
+    /// Cells tinted with the last move color of the theme.
+    pub highlights: Vec<[u8; 2]>,
+    pub annotations: Vec<Annotation>,
+    pub theme: BoardTheme,
+    /// Width and height of the picture.
+    pub size: u32,
+}
+
+impl Default for DiagramOptions {
+    fn default() -> Self {
+        Self {
+            reversed: false,
+            coordinates: true,
+            highlights: Vec::new(),
+            annotations: Vec::new(),
+            theme: BoardTheme::default(),
+            size: 360,
+        }
+    }
+}
+
+/// The color as "#rrggbb", with its opacity.
+fn svg_color(color: Color) -> (String, f32) {
+    let component = |value: f32| (value * 255.0).round() as u8;
+    (
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            component(color.r),
+            component(color.g),
+            component(color.b)
+        ),
+        color.a,
+    )
+}
+
+fn fill(color: Color) -> String {
+    let (rgb, opacity) = svg_color(color);
+    if opacity < 1.0 {
+        format!("fill=\"{}\" fill-opacity=\"{:.3}\"", rgb, opacity)
+    } else {
+        format!("fill=\"{}\"", rgb)
+    }
+}
+
+fn stroke(color: Color) -> String {
+    let (rgb, opacity) = svg_color(color);
+    if opacity < 1.0 {
+        format!("stroke=\"{}\" stroke-opacity=\"{:.3}\"", rgb, opacity)
+    } else {
+        format!("stroke=\"{}\"", rgb)
+    }
+}
+
+/// The id of the definition of a piece, named like the merida files.
+fn piece_id(piece: Piece) -> String {
+    let side = match piece.side() {
+        Side::White => 'w',
+        Side::Black => 'b',
+    };
+    format!("{}{}", side, piece.kind().letter())
+}
+
+/// The content of the root element of an SVG file.
+fn svg_content(file: &str) -> Option<&str> {
+    let root = file.find("<svg")?;
+    let content_start = root + file[root..].find('>')? + 1;
+    let content_end = file.rfind("</svg>")?;
+    file.get(content_start..content_end)
+}
+
+struct Diagram<'a> {
+    setup: &'a PositionSetup,
+    options: &'a DiagramOptions,
+    /// Width of the frame around the cells.
+    margin: f32,
+}
+
+impl<'a> Diagram<'a> {
+    fn get_cell_position(&self, cell: [u8; 2]) -> Point {
+        let [file, rank] = cell;
+        let col = if self.options.reversed {
+            7 - file
+        } else {
+            file
+        };
+        let row = if self.options.reversed {
+            rank
+        } else {
+            7 - rank
+        };
+        Point::new(
+            self.margin + CELLS_SIZE * col as f32,
+            self.margin + CELLS_SIZE * row as f32,
+        )
+    }
+
+    fn get_cell_center(&self, cell: [u8; 2]) -> Point {
+        self.get_cell_position(cell) + Vector::new(CELLS_SIZE * 0.5, CELLS_SIZE * 0.5)
+    }
+
+    fn write_pieces_definitions(&self, svg: &mut String) {
+        svg.push_str("<defs>\n");
+        for (file_name, bytes) in resources::MERIDA_PIECES.iter() {
+            let stem = file_name.trim_end_matches(".svg");
+            let used = piece_for_file_stem(stem).is_some_and(|piece| {
+                (0..64).any(|square| self.setup.piece_at([square % 8, square / 8]) == Some(piece))
+            });
+            let content = std::str::from_utf8(bytes).ok().and_then(svg_content);
+            if let (true, Some(content)) = (used, content) {
+                let _ = writeln!(svg, "<g id=\"{}\">{}</g>", stem, content);
+            }
+        }
+        svg.push_str("</defs>\n");
+    }
+
+    fn write_cells(&self, svg: &mut String) {
+        let theme = &self.options.theme;
+        for file in 0..8 {
+            for rank in 0..8 {
+                let cell = [file, rank];
+                let color = if (file + rank) % 2 == 1 {
+                    theme.light_cell
+                } else {
+                    theme.dark_cell
+                };
+                let position = self.get_cell_position(cell);
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+                    position.x,
+                    position.y,
+                    CELLS_SIZE,
+                    CELLS_SIZE,
+                    fill(color)
+                );
+                if self.options.highlights.contains(&cell) {
+                    let _ = writeln!(
+                        svg,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+                        position.x,
+                        position.y,
+                        CELLS_SIZE,
+                        CELLS_SIZE,
+                        fill(theme.last_move)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Files names above and under the cells, ranks names on both sides.
+    fn write_coordinates(&self, svg: &mut String) {
+        let end = self.margin * 2.0 + CELLS_SIZE * 8.0 - self.margin * 0.5;
+        let mut label = |x: f32, y: f32, text: char| {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" {} font-family=\"sans-serif\" font-size=\"{}\" \
+                 text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                x,
+                y,
+                fill(self.options.theme.coordinates),
+                CELLS_SIZE * 0.45,
+                text
+            );
+        };
+        for index in 0..8u8 {
+            let center = self.get_cell_center([index, index]);
+            label(center.x, self.margin * 0.5, (b'A' + index) as char);
+            label(center.x, end, (b'A' + index) as char);
+            label(self.margin * 0.5, center.y, (b'1' + index) as char);
+            label(end, center.y, (b'1' + index) as char);
+        }
+    }
+
+    fn write_pieces(&self, svg: &mut String) {
+        for file in 0..8 {
+            for rank in 0..8 {
+                let cell = [file, rank];
+                if let Some(id) = self.setup.piece_at(cell).map(piece_id) {
+                    let position = self.get_cell_position(cell);
+                    let _ = writeln!(
+                        svg,
+                        "<use xlink:href=\"#{}\" x=\"{}\" y=\"{}\"/>",
+                        id, position.x, position.y
+                    );
+                }
+            }
+        }
+    }
+
+    /// Same shapes as the arrows and circles of the board widget.
+    fn write_annotations(&self, svg: &mut String) {
+        for annotation in &self.options.annotations {
+            match *annotation {
+                Annotation::Circle { cell, color } => {
+                    let center = self.get_cell_center(cell);
+                    let border_width = CELLS_SIZE * 0.08;
+                    let _ = writeln!(
+                        svg,
+                        "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke-width=\"{}\" {}/>",
+                        center.x,
+                        center.y,
+                        (CELLS_SIZE - border_width) * 0.5,
+                        border_width,
+                        stroke(color_for_annotation(color))
+                    );
+                }
+                Annotation::Arrow { from, to, color } => {
+                    if from == to {
+                        continue;
+                    }
+                    let start = self.get_cell_center(from);
+                    let end = self.get_cell_center(to);
+                    let length = start.distance(end);
+                    let direction =
+                        Vector::new((end.x - start.x) / length, (end.y - start.y) / length);
+                    let normal = Vector::new(-direction.y, direction.x);
+
+                    let shaft_half_width = CELLS_SIZE * 0.1;
+                    let head_half_width = CELLS_SIZE * 0.25;
+                    let head_length = CELLS_SIZE * 0.4;
+                    let head_start = end - direction * head_length;
+
+                    let points: Vec<String> = [
+                        start + normal * shaft_half_width,
+                        head_start + normal * shaft_half_width,
+                        head_start + normal * head_half_width,
+                        end,
+                        head_start - normal * head_half_width,
+                        head_start - normal * shaft_half_width,
+                        start - normal * shaft_half_width,
+                    ]
+                    .iter()
+                    .map(|point| format!("{:.2},{:.2}", point.x, point.y))
+                    .collect();
+                    let _ = writeln!(
+                        svg,
+                        "<polygon points=\"{}\" {}/>",
+                        points.join(" "),
+                        fill(color_for_annotation(color))
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Draws the position of the FEN string, which doesn't need to be legal, as
+/// an SVG document.
+pub fn render_diagram(fen: &str, options: &DiagramOptions) -> Result<String, String> {
+    let setup = PositionSetup::from_fen(fen)?;
+    let margin = if options.coordinates {
+        CELLS_SIZE * 0.5
+    } else {
+        0.0
+    };
+    let diagram = Diagram {
+        setup: &setup,
+        options,
+        margin,
+    };
+    let total_size = margin * 2.0 + CELLS_SIZE * 8.0;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+         xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"1.1\" \
+         width=\"{size}\" height=\"{size}\" viewBox=\"0 0 {total} {total}\">",
+        size = options.size,
+        total = total_size
+    );
+    diagram.write_pieces_definitions(&mut svg);
+    if options.coordinates {
+        let _ = writeln!(
+            svg,
+            "<rect width=\"{}\" height=\"{}\" {}/>",
+            total_size,
+            total_size,
+            fill(options.theme.frame)
+        );
+        diagram.write_coordinates(&mut svg);
+    }
+    diagram.write_cells(&mut svg);
+    diagram.write_pieces(&mut svg);
+    diagram.write_annotations(&mut svg);
+    svg.push_str("</svg>\n");
+
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotation::AnnotationColor;
+    use crate::position_setup::START_POSITION;
+
+    #[test]
+    fn draws_every_piece_once() {
+        let svg = render_diagram(START_POSITION, &DiagramOptions::default()).unwrap();
+        assert_eq!(svg.matches("<use ").count(), 32);
+        assert_eq!(svg.matches("<g id=").count(), 12);
+        assert_eq!(svg.matches("<text ").count(), 32);
+    }
+
+    #[test]
+    fn follows_orientation() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        let options = DiagramOptions {
+            coordinates: false,
+            ..DiagramOptions::default()
+        };
+        let svg = render_diagram(fen, &options).unwrap();
+        assert!(svg.contains("<use xlink:href=\"#wR\" x=\"0\" y=\"315\"/>"));
+
+        let options = DiagramOptions {
+            reversed: true,
+            ..options
+        };
+        let svg = render_diagram(fen, &options).unwrap();
+        assert!(svg.contains("<use xlink:href=\"#wR\" x=\"315\" y=\"0\"/>"));
+        assert_eq!(svg.matches("<text ").count(), 0);
+    }
+
+    #[test]
+    fn draws_highlights_and_annotations() {
+        let options = DiagramOptions {
+            highlights: vec![[4, 1], [4, 3]],
+            annotations: vec![
+                Annotation::Arrow {
+                    from: [4, 1],
+                    to: [4, 3],
+                    color: AnnotationColor::Green,
+                },
+                Annotation::Circle {
+                    cell: [3, 4],
+                    color: AnnotationColor::Red,
+                },
+            ],
+            ..DiagramOptions::default()
+        };
+        let svg = render_diagram(START_POSITION, &options).unwrap();
+        // The pieces definitions hold circles too.
+        let (_, svg) = svg.split_at(svg.find("</defs>").unwrap());
+        assert_eq!(svg.matches("<rect ").count(), 1 + 64 + 2);
+        assert_eq!(svg.matches("<polygon ").count(), 1);
+        assert_eq!(svg.matches("<circle ").count(), 1);
+    }
+
+    #[test]
+    fn rejects_wrong_fen() {
+        assert!(render_diagram("not a position", &DiagramOptions::default()).is_err());
+    }
+}
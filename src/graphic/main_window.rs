@@ -1,70 +1,671 @@
+use arboard::Clipboard;
 use iced::{
+    executor, pick_list, time,
     widget::button::{Button, State},
-    Column, Container, Element, Length, Sandbox,
+    Align, Application, Column, Command, Container, Element, Length, PickList, Row, Subscription,
+    Text,
 };
 use iced_native::widget::{svg::Handle, Svg};
 
-use super::chess_board_component::{ChessBoard, Message as BoardMessage};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::animation::{Easing, PieceAnimation};
+use super::board_theme::BoardTheme;
+use super::chess_board::{self, ChessBoard, DragCancelled, IllegalMove, InteractionMode};
+use super::piece_set::{self, PieceSet};
+use super::position_editor::{self, PositionEditor};
+use super::resources;
+use crate::annotation::Annotation;
+use crate::chess960::{self, Variant};
+use crate::config;
+use crate::exercise::{Attempt, Collection, Exercise};
+use crate::library::Library;
+use crate::position_setup::{PositionSetup, START_POSITION};
+use crate::rules::{ChessRules, Move, RulesFactory, Side};
 
 #[derive(Debug, Clone)]
 enum Message {
     ToggleBoardOrientation,
+    ResetPosition,
+    RandomChess960Position,
+    CopyFen,
+    PasteFen,
+    NewPosition,
+    EditNewPosition(position_editor::Message),
+    ApplyNewPosition,
+    CancelNewPosition,
+    RetryExercise,
+    PreviousExercise,
+    NextExercise,
     SetPosition(String),
+    SetAnnotations(Vec<Annotation>),
+    ShowIllegalMove(IllegalMove),
+    DragCancelled(DragCancelled),
+    AnimationTick(Instant),
+    SelectPieceSet(String),
+    SelectTheme(String),
+    SelectCollection(String),
 }
 
 struct MainWindow {
-    board_position: String,
+    /// Builds the rules of the exercises.
+    new_rules: RulesFactory,
+    library: Library,
+    collection_names: Vec<String>,
+    collection_index: usize,
+    collection_pick_list_state: pick_list::State<String>,
+    /// The exercise of the collection being solved, None for a position set
+    /// up from the toolbar.
+    exercise_index: Option<usize>,
+    exercise: Exercise,
+    /// The moves played from the start position of the exercise.
+    played_moves: Vec<Move>,
+    /// The wrong moves played since the exercise was started.
+    mistakes: u32,
     board_reversed: bool,
+    board_state: chess_board::State,
+    board_annotations: Vec<Annotation>,
+    status: String,
+    position_editor: Option<PositionEditor>,
+    new_position: Option<String>,
+    board_animation: Option<PieceAnimation>,
+    animation_duration: Duration,
+    animation_easing: Easing,
+    piece_sets: Vec<Rc<PieceSet>>,
+    piece_set_names: Vec<String>,
+    selected_piece_set: Rc<PieceSet>,
+    piece_set_pick_list_state: pick_list::State<String>,
+    themes: Vec<BoardTheme>,
+    theme_names: Vec<String>,
+    selected_theme: BoardTheme,
+    theme_pick_list_state: pick_list::State<String>,
+    reverse_board_icon: Handle,
     reverse_board_button_state: State,
-    chess_board: ChessBoard,
+    reset_position_button_state: State,
+    chess960_button_state: State,
+    copy_fen_button_state: State,
+    paste_fen_button_state: State,
+    new_position_button_state: State,
+    apply_new_position_button_state: State,
+    cancel_new_position_button_state: State,
+    retry_exercise_button_state: State,
+    previous_exercise_button_state: State,
+    next_exercise_button_state: State,
+}
+
+/// The legal position whose FEN string is in the clipboard.
+fn read_clipboard_position() -> Result<PositionSetup, String> {
+    let text = Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|e| format!("Couldn't read the clipboard : {}", e))?;
+    PositionSetup::from_fen(text.trim())
+        .and_then(|setup| {
+            setup.validate().map_err(|e| e.to_string())?;
+            Ok(setup)
+        })
+        .map_err(|e| format!("Wrong position : {}", e))
+}
+
+fn write_clipboard(text: String) -> Result<(), String> {
+    Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| format!("Couldn't write the clipboard : {}", e))
+}
+
+fn text_button<'a>(state: &'a mut State, label: &str) -> Button<'a, Message> {
+    Button::new(state, Text::new(label)).width(Length::Units(110))
+}
+
+impl MainWindow {
+    fn current_rules(&self) -> Box<dyn ChessRules> {
+        let mut rules = self.exercise.game.start_rules(self.new_rules);
+        for chess_move in &self.played_moves {
+            rules.play(chess_move);
+        }
+        rules
+    }
+
+    fn collection(&self) -> Option<&Collection> {
+        self.library.collections.get(self.collection_index)
+    }
+
+    /// The number, goal and source of an exercise of the collection, and
+    /// how many times it was solved.
+    fn exercise_description(&self) -> String {
+        let (index, collection) = match (self.exercise_index, self.collection()) {
+            (Some(index), Some(collection)) if self.exercise.has_solution() => (index, collection),
+            _ => return String::new(),
+        };
+        let side = match self.exercise.user_side {
+            Side::White => "White",
+            Side::Black => "Black",
+        };
+        let mut res = format!(
+            "{} {}/{} : {} to play, {}",
+            collection.name,
+            index + 1,
+            collection.exercises.len(),
+            side,
+            self.exercise.goal.to_string().to_lowercase()
+        );
+        if let Some(difficulty) = self.exercise.difficulty {
+            res.push_str(&format!(" (difficulty {})", difficulty));
+        }
+        if let Some(ref source) = self.exercise.source {
+            res.push_str(&format!(", from {}", source));
+        }
+        if !self.exercise.history.is_empty() {
+            res.push_str(&format!(", solved {} time(s)", self.exercise.history.len()));
+        }
+        res
+    }
+
+    /// Starts the exercise, the opponent playing first when the solution
+    /// starts with its move. The board is turned to the side of the user,
+    /// unless the position is played freely.
+    fn show_exercise(&mut self, exercise: Exercise, index: Option<usize>) {
+        let old_rules = self.current_rules();
+        if exercise.has_solution() {
+            self.board_reversed = exercise.user_side == Side::Black;
+        }
+        self.exercise = exercise;
+        self.exercise_index = index;
+        self.played_moves.clear();
+        self.played_moves.extend(self.exercise.reply(&[]));
+        self.mistakes = 0;
+        self.board_state.reset();
+        self.start_board_animation(old_rules.as_ref(), self.current_rules().as_ref());
+        self.board_annotations = self
+            .exercise
+            .game
+            .comment
+            .as_ref()
+            .map(|comment| comment.annotations.clone())
+            .unwrap_or_default();
+        self.status = self.exercise_description();
+    }
+
+    /// Shows a position which doesn't follow from the current one by a move,
+    /// to be played freely.
+    fn replace_position(&mut self, fen_string: &str, variant: Variant) {
+        match Exercise::from_position(fen_string, variant) {
+            Ok(exercise) => self.show_exercise(exercise, None),
+            Err(e) => self.status = format!("Wrong position : {}", e),
+        }
+    }
+
+    fn show_collection_exercise(&mut self, index: usize) {
+        let exercise = self
+            .collection()
+            .and_then(|collection| collection.exercises.get(index))
+            .cloned();
+        if let Some(exercise) = exercise {
+            self.show_exercise(exercise, Some(index));
+        }
+    }
+
+    /// Adds the solving of the exercise to its history, and saves it into
+    /// the library.
+    fn record_attempt(&mut self) -> Result<(), String> {
+        let index = match self.exercise_index {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let attempt = Attempt {
+            time,
+            mistakes: self.mistakes,
+        };
+        self.exercise.history.push(attempt);
+        let library_exercise = self
+            .library
+            .collections
+            .get_mut(self.collection_index)
+            .and_then(|collection| collection.exercises.get_mut(index));
+        if let Some(library_exercise) = library_exercise {
+            library_exercise.history.push(attempt);
+        }
+        self.library.save()
+    }
+
+    /// Checks the move played on the board against the solution, and plays
+    /// the answer of the opponent. A wrong move is taken back, and a solved
+    /// exercise takes no more moves.
+    fn play_board_move(&mut self, fen_string: &str) {
+        if self.exercise.is_solved(&self.played_moves) {
+            return;
+        }
+        let before = self.current_rules();
+        let chess_move = before.legal_moves().into_iter().find(|legal_move| {
+            let mut after = before.clone_box();
+            after.play(legal_move) && after.fen() == fen_string
+        });
+        let chess_move = match chess_move {
+            Some(chess_move) => chess_move,
+            None => return self.replace_position(fen_string, self.exercise.game.variant),
+        };
+        let san = before.san(&chess_move).unwrap_or_default();
+        self.played_moves.push(chess_move);
+        self.board_annotations.clear();
+
+        if !self.exercise.has_solution() {
+            // The piece was dropped on its cell, nothing moves anymore.
+            self.board_animation = None;
+            self.status.clear();
+            return;
+        }
+        if !self.exercise.follows_solution(&self.played_moves) {
+            let after = self.current_rules();
+            self.played_moves.pop();
+            self.board_state.reset();
+            self.start_board_animation(after.as_ref(), before.as_ref());
+            self.mistakes += 1;
+            self.status = format!("{} is not the solution, try again", san);
+            return;
+        }
+
+        let after_user_move = self.current_rules();
+        let reply = self.exercise.reply(&self.played_moves);
+        self.played_moves.extend(reply);
+        self.start_board_animation(after_user_move.as_ref(), self.current_rules().as_ref());
+        self.status = if !self.exercise.is_solved(&self.played_moves) {
+            format!("{} is right, go on", san)
+        } else {
+            match self.record_attempt() {
+                Ok(()) => String::from("Solved !"),
+                Err(e) => format!("Solved, but the library was not saved : {} !", e),
+            }
+        };
+    }
+
+    /// The pieces the user may move: those of the side the exercise is
+    /// played with, none once it is solved, or both while playing freely.
+    fn board_interaction_mode(&self) -> InteractionMode {
+        if !self.exercise.has_solution() {
+            InteractionMode::BothSides
+        } else if self.exercise.is_solved(&self.played_moves) {
+            InteractionMode::ViewOnly
+        } else if self.exercise.user_side == Side::White {
+            InteractionMode::WhiteOnly
+        } else {
+            InteractionMode::BlackOnly
+        }
+    }
+
+    fn start_board_animation(&mut self, old_rules: &dyn ChessRules, new_rules: &dyn ChessRules) {
+        self.board_animation = Some(PieceAnimation::new(
+            old_rules,
+            new_rules,
+            self.animation_duration,
+            self.animation_easing,
+        ));
+    }
 }
 
-impl Sandbox for MainWindow {
+impl Application for MainWindow {
+    type Executor = executor::Default;
     type Message = Message;
+    type Flags = (Library, usize, RulesFactory);
 
-    fn new() -> Self {
-        Self {
-            board_position: String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    /// Starts with the first exercise of the given collection of the
+    /// library, if any.
+    fn new(
+        (library, collection_index, new_rules): (Library, usize, RulesFactory),
+    ) -> (Self, Command<Message>) {
+        let config = config::load_config();
+        let resources_directory = config.resources_directory.as_deref();
+        let piece_sets: Vec<Rc<PieceSet>> = piece_set::all_sets(resources_directory)
+            .into_iter()
+            .map(Rc::new)
+            .collect();
+        let piece_set_names = piece_sets
+            .iter()
+            .map(|piece_set| String::from(piece_set.name()))
+            .collect();
+        let selected_piece_set = piece_sets[0].clone();
+        let themes = BoardTheme::all(&config.themes);
+        let theme_names = themes.iter().map(|theme| theme.name.clone()).collect();
+        let selected_theme = themes[0].clone();
+        let exercise = match Exercise::from_position(START_POSITION, Variant::Standard) {
+            Ok(exercise) => exercise,
+            Err(e) => panic!("Wrong start position : {} !", e),
+        };
+        let collection_names = library
+            .collections
+            .iter()
+            .map(|collection| collection.name.clone())
+            .collect();
+        let mut res = Self {
+            new_rules,
+            library,
+            collection_names,
+            collection_index,
+            collection_pick_list_state: pick_list::State::default(),
+            exercise_index: None,
+            exercise,
+            played_moves: Vec::new(),
+            mistakes: 0,
             board_reversed: false,
+            board_state: chess_board::State::new(),
+            board_annotations: Vec::new(),
+            status: String::new(),
+            position_editor: None,
+            new_position: None,
+            board_animation: None,
+            animation_duration: Duration::from_millis(250),
+            animation_easing: Easing::EaseInOutCubic,
+            piece_sets,
+            piece_set_names,
+            selected_piece_set,
+            piece_set_pick_list_state: pick_list::State::default(),
+            themes,
+            theme_names,
+            selected_theme,
+            theme_pick_list_state: pick_list::State::default(),
+            reverse_board_icon: resources::reverse_arrows_icon(resources_directory),
             reverse_board_button_state: State::new(),
-            chess_board: ChessBoard::new(45u16),
-        }
+            reset_position_button_state: State::new(),
+            chess960_button_state: State::new(),
+            copy_fen_button_state: State::new(),
+            paste_fen_button_state: State::new(),
+            new_position_button_state: State::new(),
+            apply_new_position_button_state: State::new(),
+            cancel_new_position_button_state: State::new(),
+            retry_exercise_button_state: State::new(),
+            previous_exercise_button_state: State::new(),
+            next_exercise_button_state: State::new(),
+        };
+        res.show_collection_exercise(0);
+        res.board_animation = None;
+        (res, Command::none())
     }
 
     fn title(&self) -> String {
         String::from("Chess exercises organizer")
     }
 
-    fn update(&mut self, message: Message) {
+    fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::ToggleBoardOrientation => self.board_reversed = !self.board_reversed,
-            Message::SetPosition(fen_string) => self.board_position = fen_string,
+            Message::ResetPosition => self.replace_position(START_POSITION, Variant::Standard),
+            Message::RandomChess960Position => {
+                // The clock is random enough to pick a position on a click,
+                // without depending on a random number crate.
+                let index = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.subsec_nanos() % 960)
+                    .unwrap_or(518) as u16;
+                match chess960::start_position(index) {
+                    Ok(fen_string) => {
+                        self.replace_position(&fen_string, Variant::Chess960);
+                        self.status = format!("Chess960 position {}", index);
+                    }
+                    Err(e) => self.status = e,
+                }
+            }
+            Message::CopyFen => {
+                self.status = match write_clipboard(self.current_rules().fen()) {
+                    Ok(()) => String::from("FEN copied"),
+                    Err(e) => e,
+                };
+            }
+            Message::PasteFen => match read_clipboard_position() {
+                Ok(setup) => {
+                    // The castling rights of a standard position are played
+                    // the same way by both variants.
+                    let variant = if setup.has_chess960_castling() {
+                        Variant::Chess960
+                    } else {
+                        self.exercise.game.variant
+                    };
+                    self.replace_position(&setup.fen(), variant);
+                }
+                Err(e) => self.status = e,
+            },
+            Message::NewPosition => {
+                let rules = self.current_rules();
+                let fen_string = rules.fen();
+                let mut position_editor =
+                    PositionEditor::new(rules, self.selected_piece_set.clone());
+                position_editor.set_reversed(self.board_reversed);
+                position_editor.set_theme(self.selected_theme.clone());
+                self.position_editor = Some(position_editor);
+                self.new_position = Some(fen_string);
+                self.status.clear();
+            }
+            Message::EditNewPosition(message) => {
+                if let Some(ref mut position_editor) = self.position_editor {
+                    self.new_position = position_editor.update(message);
+                }
+            }
+            Message::ApplyNewPosition => {
+                if let Some(fen_string) = self.new_position.take() {
+                    self.replace_position(&fen_string, self.exercise.game.variant);
+                }
+                self.position_editor = None;
+            }
+            Message::CancelNewPosition => {
+                self.new_position = None;
+                self.position_editor = None;
+            }
+            Message::RetryExercise => {
+                self.show_exercise(self.exercise.clone(), self.exercise_index)
+            }
+            Message::PreviousExercise => {
+                if let Some(index) = self.exercise_index {
+                    self.show_collection_exercise(index.saturating_sub(1));
+                }
+            }
+            Message::NextExercise => {
+                let index = self.exercise_index.map_or(0, |index| index + 1);
+                self.show_collection_exercise(index);
+            }
+            Message::SetPosition(fen_string) => self.play_board_move(&fen_string),
+            Message::SetAnnotations(annotations) => self.board_annotations = annotations,
+            Message::ShowIllegalMove(illegal_move) => {
+                self.status = format!(
+                    "Illegal move {}{} : {}",
+                    illegal_move.from, illegal_move.to, illegal_move.reason
+                );
+            }
+            Message::DragCancelled(drag_cancelled) => {
+                self.status = format!(
+                    "Move from {} cancelled after {:.1} s",
+                    drag_cancelled.from,
+                    drag_cancelled.held.as_secs_f32()
+                );
+            }
+            Message::AnimationTick(now) => {
+                let finished = self
+                    .board_animation
+                    .as_ref()
+                    .map_or(true, |animation| animation.is_finished(now));
+                if finished {
+                    self.board_animation = None;
+                }
+            }
+            Message::SelectPieceSet(name) => {
+                let piece_set = self
+                    .piece_sets
+                    .iter()
+                    .find(|piece_set| piece_set.name() == name);
+                if let Some(piece_set) = piece_set {
+                    self.selected_piece_set = piece_set.clone();
+                    if let Some(ref mut position_editor) = self.position_editor {
+                        position_editor.set_piece_set(piece_set.clone());
+                    }
+                }
+            }
+            Message::SelectTheme(name) => {
+                let theme = self.themes.iter().find(|theme| theme.name == name);
+                if let Some(theme) = theme {
+                    self.selected_theme = theme.clone();
+                    if let Some(ref mut position_editor) = self.position_editor {
+                        position_editor.set_theme(theme.clone());
+                    }
+                }
+            }
+            Message::SelectCollection(name) => {
+                let index = self
+                    .library
+                    .collections
+                    .iter()
+                    .position(|collection| collection.name == name);
+                if let Some(index) = index {
+                    self.collection_index = index;
+                    self.show_collection_exercise(0);
+                }
+            }
         }
+        Command::none()
     }
 
-    fn view(&mut self) -> Element<Message> {
-        //let mut chess_board = ChessBoard::new(45u16);
-        //ChessBoard::new(45f32, self.board_reversed, self.board_position.clone()).on_position_changed(Box::new(|position| Message::SetPosition(position)));
-        let reverse_svg_path = format!(
-            "{}/src/graphic/resources/reverseArrows.svg",
-            env!("CARGO_MANIFEST_DIR")
-        );
-        let reverse_svg = Handle::from_path(reverse_svg_path);
-        let reverse_board_button = Button::new(
-            &mut self.reverse_board_button_state,
-            Svg::new(reverse_svg)
-                .width(Length::Units(20))
-                .height(Length::Units(20)),
-        )
-        .on_press(Message::ToggleBoardOrientation);
+    /// Redraws about 60 times per second, only while pieces are moving.
+    fn subscription(&self) -> Subscription<Message> {
+        if self.board_animation.is_some() || self.board_state.is_animating(Instant::now()) {
+            time::every(Duration::from_millis(16)).map(Message::AnimationTick)
+        } else {
+            Subscription::none()
+        }
+    }
+
+    fn view(&mut self) -> Element<'_, Message> {
+        let rules = self.current_rules();
+        let interaction_mode = self.board_interaction_mode();
+        let show_move_hints = !self.exercise.has_solution();
+        let mut settings = Row::new().spacing(10).align_items(Align::Center);
+        if let Some(collection) = self.library.collections.get(self.collection_index) {
+            settings = settings.push(Text::new("Collection")).push(PickList::new(
+                &mut self.collection_pick_list_state,
+                &self.collection_names,
+                Some(collection.name.clone()),
+                Message::SelectCollection,
+            ));
+        }
+        let settings = settings
+            .push(Text::new("Pieces"))
+            .push(PickList::new(
+                &mut self.piece_set_pick_list_state,
+                &self.piece_set_names,
+                Some(String::from(self.selected_piece_set.name())),
+                Message::SelectPieceSet,
+            ))
+            .push(Text::new("Theme"))
+            .push(PickList::new(
+                &mut self.theme_pick_list_state,
+                &self.theme_names,
+                Some(self.selected_theme.name.clone()),
+                Message::SelectTheme,
+            ));
+
+        let (board, toolbar): (Element<'_, Message>, Column<'_, Message>) = match self
+            .position_editor
+        {
+            Some(ref mut position_editor) => {
+                let mut apply_button =
+                    text_button(&mut self.apply_new_position_button_state, "Apply");
+                if self.new_position.is_some() {
+                    apply_button = apply_button.on_press(Message::ApplyNewPosition);
+                }
+                let toolbar = Column::new().spacing(10).push(apply_button).push(
+                    text_button(&mut self.cancel_new_position_button_state, "Cancel")
+                        .on_press(Message::CancelNewPosition),
+                );
+                let board = position_editor.view().map(Message::EditNewPosition);
+                (board, toolbar)
+            }
+            None => {
+                let reverse_board_button = Button::new(
+                    &mut self.reverse_board_button_state,
+                    Svg::new(self.reverse_board_icon.clone())
+                        .width(Length::Units(20))
+                        .height(Length::Units(20)),
+                )
+                .on_press(Message::ToggleBoardOrientation);
+                let toolbar = Column::new()
+                    .spacing(10)
+                    .push(reverse_board_button)
+                    .push(
+                        text_button(&mut self.reset_position_button_state, "Start position")
+                            .on_press(Message::ResetPosition),
+                    )
+                    .push(
+                        text_button(&mut self.chess960_button_state, "Chess960")
+                            .on_press(Message::RandomChess960Position),
+                    )
+                    .push(
+                        text_button(&mut self.copy_fen_button_state, "Copy FEN")
+                            .on_press(Message::CopyFen),
+                    )
+                    .push(
+                        text_button(&mut self.paste_fen_button_state, "Paste FEN")
+                            .on_press(Message::PasteFen),
+                    )
+                    .push(
+                        text_button(&mut self.new_position_button_state, "New position")
+                            .on_press(Message::NewPosition),
+                    );
+                let mut retry_button = text_button(&mut self.retry_exercise_button_state, "Retry");
+                if self.exercise.has_solution() {
+                    retry_button = retry_button.on_press(Message::RetryExercise);
+                }
+                let mut previous_button =
+                    text_button(&mut self.previous_exercise_button_state, "Previous");
+                if self.exercise_index.is_some_and(|index| index > 0) {
+                    previous_button = previous_button.on_press(Message::PreviousExercise);
+                }
+                let mut next_button = text_button(&mut self.next_exercise_button_state, "Next");
+                let next_index = self.exercise_index.map_or(0, |index| index + 1);
+                let collection_size = self
+                    .library
+                    .collections
+                    .get(self.collection_index)
+                    .map_or(0, |collection| collection.exercises.len());
+                if next_index < collection_size {
+                    next_button = next_button.on_press(Message::NextExercise);
+                }
+                let toolbar = toolbar
+                    .push(retry_button)
+                    .push(previous_button)
+                    .push(next_button);
+                let board = ChessBoard::new(
+                    &mut self.board_state,
+                    rules,
+                    self.selected_piece_set.clone(),
+                )
+                .reversed(self.board_reversed)
+                .theme(self.selected_theme.clone())
+                .annotations(self.board_annotations.clone())
+                .animation(self.board_animation.clone())
+                .interaction_mode(interaction_mode)
+                .show_move_hints(show_move_hints)
+                .on_position_changed(Box::new(Message::SetPosition))
+                .on_annotations_changed(Box::new(Message::SetAnnotations))
+                .on_illegal_move(Box::new(Message::ShowIllegalMove))
+                .on_drag_cancelled(Box::new(Message::DragCancelled))
+                .with_material_strips()
+                .into();
+                (board, toolbar)
+            }
+        };
+
         let content = Column::new()
             .padding(5)
             .spacing(20)
-            //.push(reverse_board_button)
-            .push(self.chess_board.view().map(move |message| match message {
-                BoardMessage::ToggleOrientation => Message::ToggleBoardOrientation,
-                BoardMessage::SetPosition(pos) => Message::SetPosition(pos),
-            }));
+            .height(Length::Fill)
+            .push(settings)
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .height(Length::Fill)
+                    .push(board)
+                    .push(toolbar),
+            )
+            .push(Text::new(&self.status));
 
         Container::new(content)
             .width(Length::Fill)
@@ -75,7 +676,7 @@ impl Sandbox for MainWindow {
     }
 }
 
-pub fn start() -> iced::Result {
+pub fn start(library: Library, collection_index: usize, new_rules: RulesFactory) -> iced::Result {
     let window_settings = iced::window::Settings {
         size: (800_u32, 500_u32),
         always_on_top: false,
@@ -87,7 +688,7 @@ pub fn start() -> iced::Result {
         icon: None,
     };
     MainWindow::run(iced::Settings {
-        flags: (),
+        flags: (library, collection_index, new_rules),
         window: window_settings,
         default_font: None,
         antialiasing: false,
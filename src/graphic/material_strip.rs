@@ -0,0 +1,193 @@
+use iced_graphics::{
+    Backend, Defaults, Font, HorizontalAlignment, Primitive, Renderer, VerticalAlignment,
+};
+use iced_native::{
+    layout, mouse, Color, Element, Hasher, Layout, Length, Point, Rectangle, Size, Vector, Widget,
+};
+use std::rc::Rc;
+
+use super::piece_set::PieceSet;
+use crate::rules::{ChessRules, Piece, PieceKind, Side};
+
+/// Piece types in the order they are shown, with their count in the start
+/// position and their value in pawns.
+const MATERIAL: [(PieceKind, u8, i32); 5] = [
+    (PieceKind::Queen, 1, 9),
+    (PieceKind::Rook, 2, 5),
+    (PieceKind::Bishop, 2, 3),
+    (PieceKind::Knight, 2, 3),
+    (PieceKind::Pawn, 8, 1),
+];
+
+fn count_piece(rules: &dyn ChessRules, piece: Piece) -> u8 {
+    (0..64u8)
+        .filter(|square| rules.piece_at([square % 8, square / 8]) == Some(piece))
+        .count() as u8
+}
+
+/// The pieces of the side missing from the board, most valuable first.
+/// Pieces beyond the start count come from promoted pawns, which are not
+/// counted as captured.
+fn captured_pieces_of(rules: &dyn ChessRules, side: Side) -> Vec<Piece> {
+    let mut promotions = 0u8;
+    let mut res: Vec<Piece> = Vec::new();
+    for (kind, start_count, _) in MATERIAL.iter() {
+        let count = count_piece(rules, Piece::new(side, *kind));
+        let missing = if *kind == PieceKind::Pawn {
+            start_count.saturating_sub(count).saturating_sub(promotions)
+        } else {
+            promotions += count.saturating_sub(*start_count);
+            start_count.saturating_sub(count)
+        };
+        for _ in 0..missing {
+            res.push(Piece::new(side, *kind));
+        }
+    }
+    res
+}
+
+fn material_of(rules: &dyn ChessRules, side: Side) -> i32 {
+    MATERIAL
+        .iter()
+        .map(|(kind, _, value)| i32::from(count_piece(rules, Piece::new(side, *kind))) * value)
+        .sum()
+}
+
+/// The opponent pieces a side has captured, and the material the side is
+/// ahead by, shown as "+3" when positive.
+pub struct MaterialStrip {
+    captured_pieces: Vec<Piece>,
+    advantage: i32,
+    piece_set: Rc<PieceSet>,
+    piece_size: f32,
+    text_color: Color,
+}
+
+impl MaterialStrip {
+    pub fn new(rules: &dyn ChessRules, side: Side, piece_set: Rc<PieceSet>) -> Self {
+        let opponent = side.opponent();
+        Self {
+            captured_pieces: captured_pieces_of(rules, opponent),
+            advantage: material_of(rules, side) - material_of(rules, opponent),
+            piece_set,
+            piece_size: 20.0,
+            text_color: Color::BLACK,
+        }
+    }
+
+    pub fn piece_size(mut self, piece_size: f32) -> Self {
+        self.piece_size = piece_size;
+        self
+    }
+
+    pub fn text_color(mut self, text_color: Color) -> Self {
+        self.text_color = text_color;
+        self
+    }
+
+    /// Pieces of the same type overlap, and types are a piece apart.
+    fn get_pieces_primitives(&self, layout: &Layout<'_>) -> (Vec<Primitive>, f32) {
+        let mut res: Vec<Primitive> = Vec::new();
+        let size = Size::new(self.piece_size, self.piece_size);
+
+        let mut x = 0.0;
+        let mut previous: Option<Piece> = None;
+        for piece in &self.captured_pieces {
+            if let Some(previous) = previous {
+                x += if previous == *piece {
+                    self.piece_size * 0.5
+                } else {
+                    self.piece_size
+                };
+            }
+            let position = layout.bounds().position() + Vector::new(x, 0.0);
+            res.push(
+                self.piece_set
+                    .primitive(*piece, Rectangle::new(position, size)),
+            );
+            previous = Some(*piece);
+        }
+
+        let width = if previous.is_some() {
+            x + self.piece_size
+        } else {
+            0.0
+        };
+        (res, width)
+    }
+
+    fn get_advantage_primitive(&self, layout: &Layout<'_>, left: f32) -> Primitive {
+        if self.advantage <= 0 {
+            return Primitive::None;
+        }
+
+        let bounds = layout.bounds();
+        Primitive::Text {
+            content: format!("+{}", self.advantage),
+            bounds: Rectangle::new(
+                Point::new(bounds.x + left + self.piece_size * 0.25, bounds.center_y()),
+                Size::new(bounds.width, self.piece_size),
+            ),
+            color: self.text_color,
+            size: self.piece_size * 0.8,
+            font: Font::Default,
+            horizontal_alignment: HorizontalAlignment::Left,
+            vertical_alignment: VerticalAlignment::Center,
+        }
+    }
+}
+
+impl<Message, B> Widget<Message, Renderer<B>> for MaterialStrip
+where
+    B: Backend,
+{
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Shrink
+    }
+
+    fn layout(&self, _renderer: &Renderer<B>, limits: &layout::Limits) -> layout::Node {
+        let limits = limits
+            .width(Length::Fill)
+            .height(Length::Units(self.piece_size as u16));
+        layout::Node::new(limits.resolve(Size::ZERO))
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        use std::hash::Hash;
+
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.piece_size.to_bits().hash(state);
+    }
+
+    fn draw(
+        &self,
+        _renderer: &mut Renderer<B>,
+        _defaults: &Defaults,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) -> (Primitive, mouse::Interaction) {
+        let (mut res, pieces_width) = self.get_pieces_primitives(&layout);
+        res.push(self.get_advantage_primitive(&layout, pieces_width));
+
+        (
+            Primitive::Group { primitives: res },
+            mouse::Interaction::default(),
+        )
+    }
+}
+
+impl<'a, Message, B> From<MaterialStrip> for Element<'a, Message, Renderer<B>>
+where
+    B: Backend,
+{
+    fn from(material_strip: MaterialStrip) -> Element<'a, Message, Renderer<B>> {
+        Element::new(material_strip)
+    }
+}
@@ -0,0 +1,280 @@
+use super::resources::{self, ResourceError};
+use crate::config;
+use crate::rules::Piece;
+use iced_graphics::Primitive;
+use iced_native::{image, svg, Rectangle};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The image of a piece, either vectorial or raster.
+#[derive(Debug, Clone)]
+pub enum PieceImage {
+    Svg(svg::Handle),
+    Raster(image::Handle),
+}
+
+const PIECES: [Piece; 12] = [
+    Piece::WhitePawn,
+    Piece::WhiteKnight,
+    Piece::WhiteBishop,
+    Piece::WhiteRook,
+    Piece::WhiteQueen,
+    Piece::WhiteKing,
+    Piece::BlackPawn,
+    Piece::BlackKnight,
+    Piece::BlackBishop,
+    Piece::BlackRook,
+    Piece::BlackQueen,
+    Piece::BlackKing,
+];
+
+fn piece_index(piece: Piece) -> Option<usize> {
+    PIECES.iter().position(|p| *p == piece)
+}
+
+/// Recognizes the piece of an image from its file name without extension.
+///
+/// Both the "wN" naming of the merida set and the "Chess_nlt60" naming of
+/// the CBurnett set from Wikimedia Commons are understood.
+pub fn piece_for_file_stem(stem: &str) -> Option<Piece> {
+    let letters: Vec<char> = if let Some(rest) = stem.strip_prefix("Chess_") {
+        // Chess_<piece><l|d>t<size>: light pieces are white ones.
+        let mut chars = rest.chars();
+        let piece = chars.next()?;
+        let side = match chars.next()? {
+            'l' => 'w',
+            'd' => 'b',
+            _ => return None,
+        };
+        vec![side, piece.to_ascii_uppercase()]
+    } else {
+        stem.chars().collect()
+    };
+
+    match letters.as_slice() {
+        ['w', 'P'] => Some(Piece::WhitePawn),
+        ['w', 'N'] => Some(Piece::WhiteKnight),
+        ['w', 'B'] => Some(Piece::WhiteBishop),
+        ['w', 'R'] => Some(Piece::WhiteRook),
+        ['w', 'Q'] => Some(Piece::WhiteQueen),
+        ['w', 'K'] => Some(Piece::WhiteKing),
+        ['b', 'P'] => Some(Piece::BlackPawn),
+        ['b', 'N'] => Some(Piece::BlackKnight),
+        ['b', 'B'] => Some(Piece::BlackBishop),
+        ['b', 'R'] => Some(Piece::BlackRook),
+        ['b', 'Q'] => Some(Piece::BlackQueen),
+        ['b', 'K'] => Some(Piece::BlackKing),
+        _ => None,
+    }
+}
+
+fn is_image_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    matches!(
+        extension.as_deref(),
+        Some("svg") | Some("png") | Some("jpg") | Some("jpeg") | Some("bmp") | Some("gif")
+    )
+}
+
+fn image_for_bytes(path: &Path, bytes: Vec<u8>) -> PieceImage {
+    let is_svg = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    if is_svg {
+        PieceImage::Svg(svg::Handle::from_memory(bytes))
+    } else {
+        PieceImage::Raster(image::Handle::from_memory(bytes))
+    }
+}
+
+/// A named set of images for the twelve pieces.
+#[derive(Debug, Clone)]
+pub struct PieceSet {
+    name: String,
+    images: Vec<Option<PieceImage>>,
+}
+
+fn piece_index_for_path(path: &Path) -> Option<usize> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(piece_for_file_stem)
+        .and_then(piece_index)
+}
+
+impl PieceSet {
+    /// Builds a set from images compiled into the executable, given by file
+    /// name.
+    pub fn from_memory(name: &str, files: &[(&str, &[u8])]) -> Self {
+        let mut images: Vec<Option<PieceImage>> = vec![None; PIECES.len()];
+        for (file_name, bytes) in files {
+            let path = Path::new(file_name);
+            if let Some(index) = piece_index_for_path(path) {
+                images[index] = Some(image_for_bytes(path, bytes.to_vec()));
+            }
+        }
+
+        Self {
+            name: String::from(name),
+            images,
+        }
+    }
+
+    /// Loads the images of a directory, ignoring files which are neither a
+    /// known image type nor named after a piece.
+    pub fn load_directory(name: &str, directory: &Path) -> Result<Self, ResourceError> {
+        let io_error = |source| ResourceError::Io {
+            path: directory.to_path_buf(),
+            source,
+        };
+
+        let mut images: Vec<Option<PieceImage>> = vec![None; PIECES.len()];
+        for entry in fs::read_dir(directory).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if !path.is_file() || !is_image_file(&path) {
+                continue;
+            }
+            if let Some(index) = piece_index_for_path(&path) {
+                let bytes = resources::read_file(&path)?;
+                images[index] = Some(image_for_bytes(&path, bytes));
+            }
+        }
+
+        Ok(Self {
+            name: String::from(name),
+            images,
+        })
+    }
+
+    /// Like `load_directory`, but also fails if a piece has no image.
+    pub fn load_complete_directory(name: &str, directory: &Path) -> Result<Self, ResourceError> {
+        let piece_set = Self::load_directory(name, directory)?;
+        if piece_set.is_complete() {
+            Ok(piece_set)
+        } else {
+            Err(ResourceError::IncompletePieceSet {
+                name: String::from(name),
+                path: directory.to_path_buf(),
+            })
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether every piece has an image.
+    pub fn is_complete(&self) -> bool {
+        self.images.iter().all(|image| image.is_some())
+    }
+
+    pub fn image_for(&self, piece: Piece) -> Option<&PieceImage> {
+        piece_index(piece).and_then(|index| self.images[index].as_ref())
+    }
+
+    /// The primitive drawing the piece in the given bounds, or nothing if
+    /// the set has no image for it.
+    pub fn primitive(&self, piece: Piece, bounds: Rectangle) -> Primitive {
+        match self.image_for(piece) {
+            Some(PieceImage::Svg(handle)) => Primitive::Svg {
+                handle: handle.clone(),
+                bounds,
+            },
+            Some(PieceImage::Raster(handle)) => Primitive::Image {
+                handle: handle.clone(),
+                bounds,
+            },
+            None => Primitive::None,
+        }
+    }
+}
+
+/// A bundled set, taken from the matching subdirectory of the override
+/// directory when there is one, or else from the executable.
+fn load_bundled_set(
+    name: &str,
+    subdirectory: &str,
+    files: &[(&str, &[u8])],
+    override_directory: Option<&Path>,
+) -> PieceSet {
+    if let Some(directory) = override_directory {
+        let directory = directory.join(subdirectory);
+        if directory.is_dir() {
+            match PieceSet::load_complete_directory(name, &directory) {
+                Ok(piece_set) => return piece_set,
+                Err(e) => println!("{} !", e),
+            }
+        }
+    }
+    PieceSet::from_memory(name, files)
+}
+
+/// The sets shipped with the application.
+pub fn bundled_sets(override_directory: Option<&Path>) -> Vec<PieceSet> {
+    vec![
+        load_bundled_set(
+            "Merida",
+            "merida",
+            &resources::MERIDA_PIECES,
+            override_directory,
+        ),
+        load_bundled_set(
+            "CBurnett",
+            "chess_pieces",
+            &resources::CBURNETT_PIECES,
+            override_directory,
+        ),
+    ]
+}
+
+/// Where users can add their own sets, one subdirectory per set.
+pub fn user_sets_directory() -> Option<PathBuf> {
+    config::config_directory().map(|directory| directory.join("piece_sets"))
+}
+
+/// The user sets, named after their directory. Incomplete or unreadable
+/// sets are reported and skipped.
+pub fn user_sets() -> Vec<PieceSet> {
+    let mut res: Vec<PieceSet> = Vec::new();
+
+    let directory = match user_sets_directory() {
+        Some(directory) if directory.is_dir() => directory,
+        _ => return res,
+    };
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!(
+                "Couldn't read directory {} : {} !",
+                directory.to_string_lossy(),
+                e
+            );
+            return res;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        match PieceSet::load_complete_directory(&name, &path) {
+            Ok(piece_set) => res.push(piece_set),
+            Err(e) => println!("{} !", e),
+        }
+    }
+    res.sort_by(|first, second| first.name().cmp(second.name()));
+
+    res
+}
+
+/// The bundled sets followed by the user ones.
+pub fn all_sets(override_directory: Option<&Path>) -> Vec<PieceSet> {
+    let mut res = bundled_sets(override_directory);
+    res.extend(user_sets());
+    res
+}
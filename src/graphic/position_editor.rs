@@ -0,0 +1,278 @@
+use iced::{
+    button, text_input, Align, Button, Checkbox, Color, Column, Element, Length, Radio, Row, Text,
+    TextInput,
+};
+
+use std::rc::Rc;
+
+use super::board_theme::BoardTheme;
+use super::chess_board::{self, ChessBoard};
+use super::piece_set::PieceSet;
+use crate::annotation::{cell_text, parse_cell};
+use crate::position_setup::{CastlingRight, PositionSetup};
+use crate::rules::ChessRules;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SetupChanged(PositionSetup),
+    PositionChanged(String),
+    SetWhiteTurn(bool),
+    SetCastlingRight(CastlingRight, bool),
+    SetEnPassant(String),
+    SetHalfmoveClock(String),
+    SetFullmoveNumber(String),
+    Clear,
+    StartPosition,
+}
+
+fn castling_right_label(right: CastlingRight) -> &'static str {
+    match right {
+        CastlingRight::WhiteShort => "White O-O",
+        CastlingRight::WhiteLong => "White O-O-O",
+        CastlingRight::BlackShort => "Black O-O",
+        CastlingRight::BlackLong => "Black O-O-O",
+    }
+}
+
+fn labeled_field<'a>(label: &str, input: TextInput<'a, Message>) -> Row<'a, Message> {
+    Row::new()
+        .spacing(5)
+        .align_items(Align::Center)
+        .push(Text::new(label).width(Length::Units(100)))
+        .push(input.width(Length::Units(50)).padding(3))
+}
+
+/// A board editing a position, with the parts of the FEN string which are
+/// not pieces, such as the castling rights, edited aside.
+pub struct PositionEditor {
+    setup: PositionSetup,
+    /// The rules of the position being replaced.
+    rules: Box<dyn ChessRules>,
+    reversed: bool,
+    piece_set: Rc<PieceSet>,
+    theme: BoardTheme,
+    en_passant_text: String,
+    halfmove_clock_text: String,
+    fullmove_number_text: String,
+    field_error: Option<String>,
+    board_state: chess_board::State,
+    en_passant_state: text_input::State,
+    halfmove_clock_state: text_input::State,
+    fullmove_number_state: text_input::State,
+    clear_button_state: button::State,
+    start_position_button_state: button::State,
+}
+
+impl PositionEditor {
+    /// Edits the position of the rules, or the start position if its FEN
+    /// string can't be read.
+    pub fn new(rules: Box<dyn ChessRules>, piece_set: Rc<PieceSet>) -> Self {
+        let setup = PositionSetup::from_fen(&rules.fen()).unwrap_or_else(|e| {
+            println!("Wrong position : {} !", e);
+            PositionSetup::start()
+        });
+        let mut res = Self {
+            setup: PositionSetup::empty(),
+            rules,
+            reversed: false,
+            piece_set,
+            theme: BoardTheme::default(),
+            en_passant_text: String::new(),
+            halfmove_clock_text: String::new(),
+            fullmove_number_text: String::new(),
+            field_error: None,
+            board_state: chess_board::State::new(),
+            en_passant_state: text_input::State::new(),
+            halfmove_clock_state: text_input::State::new(),
+            fullmove_number_state: text_input::State::new(),
+            clear_button_state: button::State::new(),
+            start_position_button_state: button::State::new(),
+        };
+        res.set_setup(setup);
+        res
+    }
+
+    pub fn set_reversed(&mut self, reversed: bool) {
+        self.reversed = reversed;
+    }
+
+    pub fn set_piece_set(&mut self, piece_set: Rc<PieceSet>) {
+        self.piece_set = piece_set;
+    }
+
+    pub fn set_theme(&mut self, theme: BoardTheme) {
+        self.theme = theme;
+    }
+
+    fn set_setup(&mut self, setup: PositionSetup) {
+        self.en_passant_text = setup.en_passant.map(cell_text).unwrap_or_default();
+        self.halfmove_clock_text = setup.halfmove_clock.to_string();
+        self.fullmove_number_text = setup.fullmove_number.to_string();
+        self.field_error = None;
+        self.setup = setup;
+    }
+
+    /// Applies the change, and gives the FEN string of the edited position
+    /// when it is legal.
+    pub fn update(&mut self, message: Message) -> Option<String> {
+        match message {
+            // The board sends the FEN string of legal positions along with
+            // the setup.
+            Message::PositionChanged(fen) => return Some(fen),
+            Message::SetupChanged(setup) => {
+                self.setup = setup;
+                return None;
+            }
+            Message::SetWhiteTurn(white_turn) => self.setup.white_turn = white_turn,
+            Message::SetCastlingRight(right, allowed) => {
+                self.setup.set_castling_right(right, allowed)
+            }
+            Message::SetEnPassant(text) => {
+                let text = text.trim().to_ascii_lowercase();
+                if text.is_empty() {
+                    self.setup.en_passant = None;
+                    self.field_error = None;
+                } else if let Some(cell) = parse_cell(&text) {
+                    self.setup.en_passant = Some(cell);
+                    self.field_error = None;
+                } else {
+                    self.field_error = Some(format!("{} is not a cell", text));
+                }
+                self.en_passant_text = text;
+            }
+            Message::SetHalfmoveClock(text) => {
+                match text.parse() {
+                    Ok(halfmove_clock) => {
+                        self.setup.halfmove_clock = halfmove_clock;
+                        self.field_error = None;
+                    }
+                    Err(_) => self.field_error = Some(format!("{} is not a number", text)),
+                }
+                self.halfmove_clock_text = text;
+            }
+            Message::SetFullmoveNumber(text) => {
+                match text.parse() {
+                    Ok(fullmove_number) => {
+                        self.setup.fullmove_number = fullmove_number;
+                        self.field_error = None;
+                    }
+                    Err(_) => self.field_error = Some(format!("{} is not a number", text)),
+                }
+                self.fullmove_number_text = text;
+            }
+            Message::Clear => self.set_setup(PositionSetup::empty()),
+            Message::StartPosition => self.set_setup(PositionSetup::start()),
+        }
+
+        if self.field_error.is_some() {
+            None
+        } else {
+            self.setup.validate().ok()
+        }
+    }
+
+    /// The error of a wrong field, or else why the position is illegal.
+    fn error(&self) -> Option<String> {
+        match self.field_error {
+            Some(ref error) => Some(error.clone()),
+            None => self.setup.validate().err().map(|e| e.to_string()),
+        }
+    }
+
+    pub fn view(&mut self) -> Element<'_, Message> {
+        let error = self.error();
+
+        let board = ChessBoard::setup_editor(
+            &mut self.board_state,
+            self.setup.clone(),
+            self.rules.clone(),
+            self.piece_set.clone(),
+        )
+        .reversed(self.reversed)
+        .theme(self.theme.clone())
+        .on_setup_changed(Box::new(Message::SetupChanged))
+        .on_position_changed(Box::new(Message::PositionChanged));
+
+        let side_to_move = Column::new()
+            .spacing(5)
+            .push(Text::new("Side to move"))
+            .push(Radio::new(
+                true,
+                "White",
+                Some(self.setup.white_turn),
+                Message::SetWhiteTurn,
+            ))
+            .push(Radio::new(
+                false,
+                "Black",
+                Some(self.setup.white_turn),
+                Message::SetWhiteTurn,
+            ));
+
+        let mut castling_rights = Column::new().spacing(5).push(Text::new("Castling"));
+        for right in CastlingRight::ALL.iter().copied() {
+            castling_rights = castling_rights.push(Checkbox::new(
+                self.setup.has_castling_right(right),
+                castling_right_label(right),
+                move |allowed| Message::SetCastlingRight(right, allowed),
+            ));
+        }
+
+        let en_passant = labeled_field(
+            "En passant",
+            TextInput::new(
+                &mut self.en_passant_state,
+                "-",
+                &self.en_passant_text,
+                Message::SetEnPassant,
+            ),
+        );
+        let halfmove_clock = labeled_field(
+            "Halfmove clock",
+            TextInput::new(
+                &mut self.halfmove_clock_state,
+                "0",
+                &self.halfmove_clock_text,
+                Message::SetHalfmoveClock,
+            ),
+        );
+        let fullmove_number = labeled_field(
+            "Fullmove number",
+            TextInput::new(
+                &mut self.fullmove_number_state,
+                "1",
+                &self.fullmove_number_text,
+                Message::SetFullmoveNumber,
+            ),
+        );
+
+        let buttons = Row::new()
+            .spacing(10)
+            .push(
+                Button::new(&mut self.clear_button_state, Text::new("Clear"))
+                    .on_press(Message::Clear),
+            )
+            .push(
+                Button::new(
+                    &mut self.start_position_button_state,
+                    Text::new("Start position"),
+                )
+                .on_press(Message::StartPosition),
+            );
+
+        let mut controls = Column::new()
+            .spacing(15)
+            .width(Length::Shrink)
+            .push(side_to_move)
+            .push(castling_rights)
+            .push(en_passant)
+            .push(halfmove_clock)
+            .push(fullmove_number)
+            .push(buttons);
+        if let Some(error) = error {
+            controls = controls.push(Text::new(error).color(Color::from_rgb8(200, 30, 30)));
+        }
+
+        Row::new().spacing(20).push(board).push(controls).into()
+    }
+}
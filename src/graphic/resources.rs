@@ -0,0 +1,135 @@
+//! Images compiled into the executable, so that it does not depend on the
+//! source tree, and the errors met when loading replacements from disk.
+
+use iced_native::svg;
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The merida vectorial pieces, by file name.
+pub const MERIDA_PIECES: [(&str, &[u8]); 12] = [
+    ("bB.svg", include_bytes!("resources/merida/bB.svg")),
+    ("bK.svg", include_bytes!("resources/merida/bK.svg")),
+    ("bN.svg", include_bytes!("resources/merida/bN.svg")),
+    ("bP.svg", include_bytes!("resources/merida/bP.svg")),
+    ("bQ.svg", include_bytes!("resources/merida/bQ.svg")),
+    ("bR.svg", include_bytes!("resources/merida/bR.svg")),
+    ("wB.svg", include_bytes!("resources/merida/wB.svg")),
+    ("wK.svg", include_bytes!("resources/merida/wK.svg")),
+    ("wN.svg", include_bytes!("resources/merida/wN.svg")),
+    ("wP.svg", include_bytes!("resources/merida/wP.svg")),
+    ("wQ.svg", include_bytes!("resources/merida/wQ.svg")),
+    ("wR.svg", include_bytes!("resources/merida/wR.svg")),
+];
+
+/// The CBurnett raster pieces from Wikimedia Commons, by file name.
+pub const CBURNETT_PIECES: [(&str, &[u8]); 12] = [
+    (
+        "Chess_bdt60.png",
+        include_bytes!("resources/chess_pieces/Chess_bdt60.png"),
+    ),
+    (
+        "Chess_blt60.png",
+        include_bytes!("resources/chess_pieces/Chess_blt60.png"),
+    ),
+    (
+        "Chess_kdt60.png",
+        include_bytes!("resources/chess_pieces/Chess_kdt60.png"),
+    ),
+    (
+        "Chess_klt60.png",
+        include_bytes!("resources/chess_pieces/Chess_klt60.png"),
+    ),
+    (
+        "Chess_ndt60.png",
+        include_bytes!("resources/chess_pieces/Chess_ndt60.png"),
+    ),
+    (
+        "Chess_nlt60.png",
+        include_bytes!("resources/chess_pieces/Chess_nlt60.png"),
+    ),
+    (
+        "Chess_pdt60.png",
+        include_bytes!("resources/chess_pieces/Chess_pdt60.png"),
+    ),
+    (
+        "Chess_plt60.png",
+        include_bytes!("resources/chess_pieces/Chess_plt60.png"),
+    ),
+    (
+        "Chess_qdt60.png",
+        include_bytes!("resources/chess_pieces/Chess_qdt60.png"),
+    ),
+    (
+        "Chess_qlt60.png",
+        include_bytes!("resources/chess_pieces/Chess_qlt60.png"),
+    ),
+    (
+        "Chess_rdt60.png",
+        include_bytes!("resources/chess_pieces/Chess_rdt60.png"),
+    ),
+    (
+        "Chess_rlt60.png",
+        include_bytes!("resources/chess_pieces/Chess_rlt60.png"),
+    ),
+];
+
+const REVERSE_ARROWS_ICON: &[u8] = include_bytes!("resources/reverseArrows.svg");
+
+/// An asset which could not be loaded from disk.
+#[derive(Debug)]
+pub enum ResourceError {
+    Io { path: PathBuf, source: io::Error },
+    IncompletePieceSet { name: String, path: PathBuf },
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceError::Io { path, source } => {
+                write!(f, "couldn't read {}: {}", path.to_string_lossy(), source)
+            }
+            ResourceError::IncompletePieceSet { name, path } => write!(
+                f,
+                "piece set {} in {} misses some pieces",
+                name,
+                path.to_string_lossy()
+            ),
+        }
+    }
+}
+
+impl Error for ResourceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResourceError::Io { source, .. } => Some(source),
+            ResourceError::IncompletePieceSet { .. } => None,
+        }
+    }
+}
+
+/// Reads a whole file, keeping its path in the error.
+pub fn read_file(path: &Path) -> Result<Vec<u8>, ResourceError> {
+    fs::read(path).map_err(|source| ResourceError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// The board orientation icon, taken from the override directory when it
+/// holds one.
+pub fn reverse_arrows_icon(override_directory: Option<&Path>) -> svg::Handle {
+    if let Some(directory) = override_directory {
+        let path = directory.join("reverseArrows.svg");
+        if path.is_file() {
+            match read_file(&path) {
+                Ok(bytes) => return svg::Handle::from_memory(bytes),
+                Err(e) => println!("{} !", e),
+            }
+        }
+    }
+    svg::Handle::from_memory(REVERSE_ARROWS_ICON)
+}
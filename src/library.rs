@@ -0,0 +1,367 @@
+//! The library of the user: the imported collections and the history of
+//! their exercises, saved in the data directory as a versioned TOML file.
+
+use crate::config;
+use crate::exercise::{Attempt, Collection, Exercise};
+use crate::pgn;
+use crate::rules::RulesFactory;
+
+use serde::{Deserialize, Serialize};
+use toml::value::Table;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const LIBRARY_FILE: &str = "library.toml";
+
+/// Upgrades the content of a library file to the next version.
+type Migration = fn(&mut Table) -> Result<(), String>;
+
+/// The migration at index i upgrades the files of version i + 1. Changing
+/// the format of the file means adding its migration here.
+const MIGRATIONS: [Migration; 0] = [];
+
+/// The version of the files written by this program.
+pub const LIBRARY_VERSION: i64 = MIGRATIONS.len() as i64 + 1;
+
+/// An exercise as saved: its game in PGN, the fields of the exercise being
+/// in the tags, and its history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExerciseRecord {
+    pgn: String,
+    #[serde(default)]
+    history: Vec<Attempt>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CollectionRecord {
+    name: String,
+    #[serde(default)]
+    exercises: Vec<ExerciseRecord>,
+}
+
+/// The content of the library file.
+///
+/// ```toml
+/// version = 1
+///
+/// [[collections]]
+/// name = "Opera game"
+///
+/// [[collections.exercises]]
+/// pgn = '''
+/// [UserSide "White"]
+/// ...
+/// '''
+///
+/// [[collections.exercises.history]]
+/// time = 1700000000
+/// mistakes = 1
+/// ```
+#[derive(Serialize, Deserialize)]
+struct LibraryFile {
+    version: i64,
+    #[serde(default)]
+    collections: Vec<CollectionRecord>,
+}
+
+/// Applies the migrations the content of a file needs, and gives the
+/// version it had. Files written by a newer program can't be read.
+fn migrate(table: &mut Table, migrations: &[Migration]) -> Result<i64, String> {
+    let current = migrations.len() as i64 + 1;
+    let version = table
+        .get("version")
+        .and_then(toml::Value::as_integer)
+        .ok_or_else(|| String::from("No version"))?;
+    if version < 1 || version > current {
+        return Err(format!(
+            "Unsupported version {}, the last known one is {}",
+            version, current
+        ));
+    }
+    for (index, migration) in migrations.iter().enumerate().skip(version as usize - 1) {
+        let next_version = index as i64 + 2;
+        migration(table)
+            .map_err(|e| format!("Upgrade to version {} failed : {}", next_version, e))?;
+        table.insert(String::from("version"), toml::Value::Integer(next_version));
+    }
+    Ok(version)
+}
+
+fn read_exercise(
+    collection: &str,
+    record: &ExerciseRecord,
+    new_rules: RulesFactory,
+) -> Result<Exercise, String> {
+    let mut file = pgn::parse(collection, &record.pgn, new_rules);
+    if let Some(e) = file.errors.first() {
+        return Err(e.to_string());
+    }
+    if file.games.len() != 1 {
+        return Err(format!(
+            "{} : an exercise holds {} games instead of one",
+            collection,
+            file.games.len()
+        ));
+    }
+    let mut res = Exercise::from_game(file.games.remove(0));
+    res.history = record.history.clone();
+    Ok(res)
+}
+
+/// The collections of the user. A library without file can't be saved.
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    path: Option<PathBuf>,
+    pub collections: Vec<Collection>,
+    /// The exercises of the file which couldn't be read, with the name of
+    /// their collection, written back unchanged at the end of it.
+    unreadable: Vec<(String, ExerciseRecord)>,
+    /// Why the unreadable exercises couldn't be read.
+    pub errors: Vec<String>,
+}
+
+impl Library {
+    /// Reads the library file, a missing one giving an empty library. A
+    /// file of an older version is copied under its version, then upgraded.
+    /// The exercises which can't be read are left out and kept as they are.
+    pub fn open(path: &Path, new_rules: RulesFactory) -> Result<Self, String> {
+        let mut res = Self {
+            path: Some(path.to_path_buf()),
+            ..Self::default()
+        };
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(res),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut table: Table = toml::from_str(&content).map_err(|e| e.to_string())?;
+        let version = migrate(&mut table, &MIGRATIONS)?;
+        let file: LibraryFile = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        for record in file.collections {
+            let mut collection = Collection::new(&record.name);
+            for exercise_record in record.exercises {
+                match read_exercise(&record.name, &exercise_record, new_rules) {
+                    Ok(exercise) => collection.exercises.push(exercise),
+                    Err(e) => {
+                        res.errors.push(e);
+                        res.unreadable.push((record.name.clone(), exercise_record));
+                    }
+                }
+            }
+            res.collections.push(collection);
+        }
+
+        if version < LIBRARY_VERSION {
+            let backup_path = path.with_extension(format!("v{}.toml", version));
+            fs::copy(path, backup_path).map_err(|e| e.to_string())?;
+            res.save()?;
+        }
+        Ok(res)
+    }
+
+    /// Writes the whole library into a temporary file which then replaces
+    /// the library file, so that a failed write leaves the previous library.
+    pub fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Err(String::from("No library file")),
+        };
+        let mut file = LibraryFile {
+            version: LIBRARY_VERSION,
+            collections: self
+                .collections
+                .iter()
+                .map(|collection| CollectionRecord {
+                    name: collection.name.clone(),
+                    exercises: collection
+                        .exercises
+                        .iter()
+                        .map(|exercise| ExerciseRecord {
+                            pgn: pgn::write_game(&exercise.to_game()),
+                            history: exercise.history.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        for (name, record) in &self.unreadable {
+            let index = match file
+                .collections
+                .iter()
+                .position(|collection| collection.name == *name)
+            {
+                Some(index) => index,
+                None => {
+                    file.collections.push(CollectionRecord {
+                        name: name.clone(),
+                        exercises: Vec::new(),
+                    });
+                    file.collections.len() - 1
+                }
+            };
+            file.collections[index].exercises.push(record.clone());
+        }
+        let content = toml::to_string_pretty(&file).map_err(|e| e.to_string())?;
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        let temporary_path = path.with_extension("toml.tmp");
+        let mut temporary = fs::File::create(&temporary_path).map_err(|e| e.to_string())?;
+        temporary
+            .write_all(content.as_bytes())
+            .and_then(|_| temporary.sync_all())
+            .map_err(|e| e.to_string())?;
+        fs::rename(&temporary_path, path).map_err(|e| e.to_string())
+    }
+
+    /// Adds the collection, replacing the one of the same name but keeping
+    /// the history of the exercises it shares with it. Gives the index of
+    /// the collection. A collection without exercises never replaces
+    /// another one.
+    pub fn import(&mut self, mut collection: Collection) -> Result<usize, String> {
+        let index = self
+            .collections
+            .iter()
+            .position(|old_collection| old_collection.name == collection.name);
+        match index {
+            Some(_) if collection.exercises.is_empty() => Err(format!(
+                "No exercise read, the collection {} is kept",
+                collection.name
+            )),
+            Some(index) => {
+                let old_exercises = &self.collections[index].exercises;
+                for exercise in &mut collection.exercises {
+                    let old_exercise = old_exercises
+                        .iter()
+                        .find(|old_exercise| old_exercise.game == exercise.game);
+                    if let Some(old_exercise) = old_exercise {
+                        exercise.history = old_exercise.history.clone();
+                    }
+                }
+                self.collections[index] = collection;
+                Ok(index)
+            }
+            None => {
+                self.collections.push(collection);
+                Ok(self.collections.len() - 1)
+            }
+        }
+    }
+}
+
+/// Opens the library of the data directory. A library which can't be read
+/// is reported and replaced with an empty one which can't be saved, so that
+/// the file is left untouched.
+pub fn open_library(new_rules: RulesFactory) -> Library {
+    let path = match config::data_directory() {
+        Some(directory) => directory.join(LIBRARY_FILE),
+        None => return Library::default(),
+    };
+    let res = Library::open(&path, new_rules).unwrap_or_else(|e| {
+        println!(
+            "Wrong library file {} : {}, nothing will be saved !",
+            path.to_string_lossy(),
+            e
+        );
+        Library::default()
+    });
+    for e in &res.errors {
+        println!("Exercise of the library left out : {} !", e);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::pleco_rules;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let res = std::env::temp_dir().join(format!("library-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&res);
+        res
+    }
+
+    #[test]
+    fn saves_and_reopens_with_history() {
+        let directory = test_directory("save");
+        let path = directory.join(LIBRARY_FILE);
+        let text = include_str!("pgn/testdata/exercises.pgn");
+        let collection =
+            Collection::from_pgn("exercises", pgn::parse("exercises.pgn", text, pleco_rules));
+        assert!(!collection.exercises.is_empty());
+
+        let mut library = Library::open(&path, pleco_rules).unwrap();
+        assert!(library.collections.is_empty());
+        let index = library.import(collection.clone()).unwrap();
+        library.collections[index].exercises[0]
+            .history
+            .push(Attempt {
+                time: 1_700_000_000,
+                mistakes: 2,
+            });
+        library.save().unwrap();
+        assert!(!path.with_extension("toml.tmp").exists());
+        assert!(Library::default().save().is_err());
+
+        let mut reopened = Library::open(&path, pleco_rules).unwrap();
+        assert_eq!(reopened.collections, library.collections);
+        // Importing the file again keeps the history.
+        assert_eq!(reopened.import(collection), Ok(index));
+        assert_eq!(reopened.collections, library.collections);
+        assert!(reopened.import(Collection::new("exercises")).is_err());
+        assert_eq!(reopened.collections, library.collections);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn keeps_unreadable_exercises() {
+        let directory = test_directory("unreadable");
+        let path = directory.join(LIBRARY_FILE);
+        fs::create_dir_all(&directory).unwrap();
+        let content = "version = 1\n\
+                       [[collections]]\nname = \"a\"\n\
+                       [[collections.exercises]]\npgn = \"1. e4 e5 *\"\n\
+                       [[collections.exercises]]\npgn = \"1. e5 *\"\n";
+        fs::write(&path, content).unwrap();
+
+        let library = Library::open(&path, pleco_rules).unwrap();
+        assert_eq!(library.collections[0].exercises.len(), 1);
+        assert_eq!(library.errors.len(), 1);
+        library.save().unwrap();
+        let reopened = Library::open(&path, pleco_rules).unwrap();
+        assert_eq!(reopened.collections, library.collections);
+        assert_eq!(reopened.errors, library.errors);
+        assert!(fs::read_to_string(&path).unwrap().contains("1. e5 *"));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    fn rename_collections(table: &mut Table) -> Result<(), String> {
+        let collections = table
+            .remove("groups")
+            .ok_or_else(|| String::from("No groups"))?;
+        table.insert(String::from("collections"), collections);
+        Ok(())
+    }
+
+    #[test]
+    fn migrates_older_versions_and_refuses_newer_ones() {
+        let migrations: [Migration; 1] = [rename_collections];
+        let mut table: Table = toml::from_str("version = 1\n[[groups]]\nname = \"a\"").unwrap();
+        assert_eq!(migrate(&mut table, &migrations), Ok(1));
+        assert_eq!(table["version"].as_integer(), Some(2));
+        assert!(table.contains_key("collections"));
+        assert_eq!(migrate(&mut table, &migrations), Ok(2));
+
+        let mut table: Table = toml::from_str("version = 3").unwrap();
+        assert!(migrate(&mut table, &migrations).is_err());
+        let mut table: Table = toml::from_str("version = 1").unwrap();
+        assert!(migrate(&mut table, &migrations).is_err());
+    }
+}
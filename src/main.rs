@@ -1,8 +1,119 @@
+mod annotation;
+mod chess960;
+mod config;
+mod exercise;
 mod graphic;
-use graphic::start;
+mod library;
+mod pgn;
+mod position_setup;
+mod rules;
+use graphic::{render_diagram, start, DiagramOptions};
 
-use iced;
+use exercise::Collection;
+use library::Library;
+use rules::RulesFactory;
+
+use std::fs;
+use std::path::Path;
+
+const DIAGRAM_USAGE: &str = "Usage : diagram [--flip] [--no-coordinates] [--size <pixels>] \
+[--highlight <cell>]... [--annotations \"[%cal Ge2e4][%csl Rd5]\"] [--output <file>] <FEN>";
+
+/// Writes the SVG diagram of a position to the standard output, or to the
+/// given file.
+fn export_diagram(args: &[String]) -> Result<(), String> {
+    let mut options = DiagramOptions::default();
+    let mut output: Option<&str> = None;
+    let mut fen: Option<&str> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--flip" => options.reversed = true,
+            "--no-coordinates" => options.coordinates = false,
+            "--size" => {
+                let size = value()?;
+                options.size = size
+                    .parse()
+                    .map_err(|_| format!("{} is not a size", size))?;
+            }
+            "--highlight" => {
+                let cell = value()?;
+                options.highlights.push(
+                    annotation::parse_cell(cell)
+                        .ok_or_else(|| format!("{} is not a cell", cell))?,
+                );
+            }
+            "--annotations" => {
+                let (_, annotations) = annotation::parse_comment(value()?);
+                options.annotations.extend(annotations);
+            }
+            "--output" => output = Some(value()?),
+            _ if fen.is_none() => fen = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    let fen = fen.ok_or_else(|| String::from(DIAGRAM_USAGE))?;
+    let svg = render_diagram(fen, &options)?;
+    match output {
+        Some(path) => fs::write(path, svg).map_err(|e| format!("Couldn't write {} : {}", path, e)),
+        None => {
+            print!("{}", svg);
+            Ok(())
+        }
+    }
+}
+
+/// The exercises of a PGN file, named after the file. The games which can't
+/// be read are reported and left out.
+fn load_collection(path: &Path, new_rules: RulesFactory) -> Result<Collection, String> {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file = pgn::read_file(path, new_rules)?;
+    for e in &file.errors {
+        eprintln!("{} !", e);
+    }
+    Ok(Collection::from_pgn(&name, file))
+}
+
+/// Adds the exercises of a PGN file to the library and saves it. Gives the
+/// index of their collection.
+fn import_collection(
+    library: &mut Library,
+    path: &Path,
+    new_rules: RulesFactory,
+) -> Result<usize, String> {
+    let index = library.import(load_collection(path, new_rules)?)?;
+    library
+        .save()
+        .map_err(|e| format!("Library not saved : {}", e))?;
+    Ok(index)
+}
 
 pub fn main() -> iced::Result {
-    start()
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("diagram") {
+        if let Err(e) = export_diagram(&args[1..]) {
+            eprintln!("{} !", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let new_rules: RulesFactory = rules::pleco_rules;
+    let mut library = library::open_library(new_rules);
+    let collection_index = match args.first() {
+        Some(path) => {
+            import_collection(&mut library, Path::new(path), new_rules).unwrap_or_else(|e| {
+                eprintln!("{} !", e);
+                0
+            })
+        }
+        None => 0,
+    };
+    start(library, collection_index, new_rules)
 }
@@ -0,0 +1,283 @@
+//! Portable Game Notation: games with their variations, comments and
+//! annotation glyphs, read from files holding any number of games.
+
+mod reader;
+mod writer;
+
+pub use reader::{parse, read_file};
+pub use writer::write_game;
+
+use crate::annotation::{self, Annotation};
+use crate::chess960::Variant;
+use crate::position_setup::{PositionSetup, START_POSITION};
+use crate::rules::{ChessRules, Move, RulesFactory};
+
+use std::time::Duration;
+
+/// The tags every PGN game should have, in their export order.
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    pub fn from_text(text: &str) -> Option<Self> {
+        match text {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn text(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        }
+    }
+}
+
+/// Reads the time of a `%clk` command, such as "1:02:03" or "0:00:09.8".
+fn parse_clock(text: &str) -> Option<Duration> {
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let hours: u64 = parts[0].parse().ok()?;
+    let minutes: u64 = parts[1].parse().ok()?;
+    let mut seconds_parts = parts[2].splitn(2, '.');
+    let seconds: u64 = seconds_parts.next()?.parse().ok()?;
+    let millis = match seconds_parts.next() {
+        Some(fraction) if !fraction.is_empty() && fraction.len() <= 3 => {
+            let digits: u64 = fraction.parse().ok()?;
+            digits * 10u64.pow(3 - fraction.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+    Some(Duration::from_millis(
+        ((hours * 60 + minutes) * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+/// Writes a time for the `%clk` command, with the fraction of seconds only
+/// when there is one.
+fn format_clock(clock: Duration) -> String {
+    let seconds = clock.as_secs();
+    let mut res = format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    let millis = clock.subsec_millis();
+    if millis > 0 {
+        let fraction = format!("{:03}", millis);
+        res.push('.');
+        res.push_str(fraction.trim_end_matches('0'));
+    }
+    res
+}
+
+/// A comment split into its text, the arrows and circles of its `%cal` and
+/// `%csl` commands and the remaining clock time of its `%clk` command.
+/// Other commands stay in the text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub annotations: Vec<Annotation>,
+    pub clock: Option<Duration>,
+}
+
+impl Comment {
+    /// Reads a comment, its circles coming before its arrows as they are
+    /// written by `pgn_text`.
+    pub fn parse(comment: &str) -> Self {
+        let (mut text, mut annotations) = annotation::parse_comment(comment);
+        annotations.sort_by_key(|annotation| matches!(annotation, Annotation::Arrow { .. }));
+
+        let mut clock = None;
+        if let Some(start) = text.find("[%clk") {
+            if let Some(end) = text[start..].find(']').map(|end| start + end) {
+                clock = parse_clock(text[start + 5..end].trim());
+                if clock.is_some() {
+                    text.replace_range(start..=end, "");
+                    text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                }
+            }
+        }
+
+        Self {
+            text,
+            annotations,
+            clock,
+        }
+    }
+
+    /// The comment as written between braces, commands last.
+    pub fn pgn_text(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if !self.text.is_empty() {
+            parts.push(self.text.clone());
+        }
+        let commands = annotation::format_commands(&self.annotations);
+        if !commands.is_empty() {
+            parts.push(commands);
+        }
+        if let Some(clock) = self.clock {
+            parts.push(format!("[%clk {}]", format_clock(clock)));
+        }
+        parts.join(" ")
+    }
+
+    /// Adds a comment following this one, as when a move has several.
+    fn append(&mut self, other: Comment) {
+        if !other.text.is_empty() {
+            if !self.text.is_empty() {
+                self.text.push(' ');
+            }
+            self.text.push_str(&other.text);
+        }
+        self.annotations.extend(other.annotations);
+        self.clock = self.clock.or(other.clock);
+    }
+}
+
+/// A move of a game, with the lines which could have been played instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveNode {
+    pub chess_move: Move,
+    /// The move as written by the rules, whatever its spelling in the file.
+    pub san: String,
+    /// Numeric annotation glyphs, "!" being 1 and "?" 2.
+    pub nags: Vec<u8>,
+    /// A comment written before the move, which only happens at the start
+    /// of variations.
+    pub starting_comment: Option<Comment>,
+    pub comment: Option<Comment>,
+    /// Lines starting with another move instead of this one.
+    pub variations: Vec<Vec<MoveNode>>,
+}
+
+impl MoveNode {
+    fn new(chess_move: Move, san: String) -> Self {
+        Self {
+            chess_move,
+            san,
+            nags: Vec::new(),
+            starting_comment: None,
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// A game and its variations, starting from the position of its FEN tag
+/// or else from the standard start position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    /// The tag pairs, in the order given by `ordered_tags`.
+    pub tags: Vec<(String, String)>,
+    pub variant: Variant,
+    /// The FEN string of the start position.
+    pub start_position: String,
+    /// The comment before the first move.
+    pub comment: Option<Comment>,
+    pub moves: Vec<MoveNode>,
+    pub result: GameResult,
+}
+
+impl Game {
+    /// A game without tags nor moves starting from the legal position.
+    pub fn from_position(fen: &str, variant: Variant) -> Result<Self, String> {
+        let start_position = PositionSetup::from_fen(fen)
+            .and_then(|setup| setup.validate().map_err(|e| e.to_string()))?;
+        Ok(Self {
+            tags: Vec::new(),
+            variant,
+            start_position,
+            comment: None,
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        })
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The tags in their export order: the seven tag roster, then the
+    /// Variant, SetUp and FEN tags, written from the variant and the start
+    /// position, then the other tags in their order.
+    pub fn ordered_tags(&self) -> Vec<(String, String)> {
+        let mut res: Vec<(String, String)> = Vec::new();
+        for name in SEVEN_TAG_ROSTER.iter() {
+            if let Some(value) = self.tag(name) {
+                res.push((String::from(*name), String::from(value)));
+            }
+        }
+        if let Some(variant) = self.variant.tag() {
+            res.push((String::from("Variant"), String::from(variant)));
+        }
+        if self.start_position != START_POSITION {
+            res.push((String::from("SetUp"), String::from("1")));
+            res.push((String::from("FEN"), self.start_position.clone()));
+        }
+        for (name, value) in &self.tags {
+            let derived = ["Variant", "SetUp", "FEN"].contains(&name.as_str());
+            if !derived && !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                res.push((name.clone(), value.clone()));
+            }
+        }
+        res
+    }
+
+    pub fn start_rules(&self, new_rules: RulesFactory) -> Box<dyn ChessRules> {
+        new_rules(&self.start_position).unwrap_or_else(|e| {
+            println!("Wrong position : {} !", e);
+            new_rules(START_POSITION).unwrap_or_else(|e| panic!("Wrong start position : {} !", e))
+        })
+    }
+}
+
+/// Why a game of a PGN file could not be read. Lines and columns start at
+/// 1, and so do games.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub file: String,
+    pub game: usize,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{} (game {}) : {}",
+            self.file, self.line, self.column, self.game, self.message
+        )
+    }
+}
+
+/// The games read from a file, and the errors of the games which could not
+/// be read.
+#[derive(Debug, Clone, Default)]
+pub struct PgnFile {
+    pub games: Vec<Game>,
+    pub errors: Vec<ParseError>,
+}
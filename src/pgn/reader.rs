@@ -0,0 +1,587 @@
+//! Splits PGN text into tokens, the tokens into games, and checks the moves
+//! of each game against the rules.
+
+use super::{Comment, Game, GameResult, MoveNode, ParseError, PgnFile};
+use crate::chess960::Variant;
+use crate::position_setup::{PositionSetup, START_POSITION};
+use crate::rules::{ChessRules, RulesFactory};
+
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    TagPair(String, String),
+    Comment(String),
+    StartVariation,
+    EndVariation,
+    Nag(u8),
+    MoveNumber,
+    San(String),
+    Result(GameResult),
+    /// Text which is not PGN, reported once its game is parsed.
+    Invalid(String),
+}
+
+struct Located {
+    token: Token,
+    line: usize,
+    column: usize,
+}
+
+/// The glyph number of the "!" and "?" suffixes.
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn is_symbol_letter(letter: char) -> bool {
+    letter.is_ascii_alphanumeric() || matches!(letter, '+' | '#' | '=' | ':' | '-' | '/' | '_')
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let letter = self.chars.next()?;
+        if letter == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(letter)
+    }
+
+    fn take_while(&mut self, accepted: impl Fn(char) -> bool) -> String {
+        let mut res = String::new();
+        while let Some(&letter) = self.chars.peek() {
+            if !accepted(letter) {
+                break;
+            }
+            res.push(letter);
+            self.bump();
+        }
+        res
+    }
+
+    fn skip_line(&mut self) -> String {
+        let res = self.take_while(|letter| letter != '\n');
+        self.bump();
+        res
+    }
+
+    /// Reads `[Name "value"]`, the opening bracket being already read.
+    fn tag_pair(&mut self) -> Token {
+        self.take_while(char::is_whitespace);
+        let name = self.take_while(|letter| letter.is_ascii_alphanumeric() || letter == '_');
+        self.take_while(char::is_whitespace);
+        if name.is_empty() || self.bump() != Some('"') {
+            self.skip_line();
+            return Token::Invalid(String::from("malformed tag pair"));
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => value.extend(self.bump()),
+                Some('\n') | None => {
+                    return Token::Invalid(format!("unterminated value of tag {}", name))
+                }
+                Some(letter) => value.push(letter),
+            }
+        }
+
+        self.take_while(|letter| letter != ']' && letter != '\n');
+        if self.bump() != Some(']') {
+            return Token::Invalid(format!("unterminated tag {}", name));
+        }
+        Token::TagPair(name, value)
+    }
+
+    fn brace_comment(&mut self) -> Token {
+        let comment = self.take_while(|letter| letter != '}');
+        match self.bump() {
+            Some(_) => Token::Comment(comment),
+            None => Token::Invalid(String::from("unterminated comment")),
+        }
+    }
+
+    fn nag(&mut self) -> Token {
+        let digits = self.take_while(|letter| letter.is_ascii_digit());
+        match digits.parse() {
+            Ok(nag) => Token::Nag(nag),
+            Err(_) => Token::Invalid(format!("${} is not a glyph", digits)),
+        }
+    }
+
+    fn symbol(&mut self) -> Token {
+        let symbol = self.take_while(is_symbol_letter);
+        if symbol.chars().all(|letter| letter.is_ascii_digit()) {
+            self.take_while(|letter| letter == '.');
+            return Token::MoveNumber;
+        }
+        match GameResult::from_text(&symbol) {
+            Some(result) => Token::Result(result),
+            None => Token::San(symbol),
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Located> {
+        self.take_while(char::is_whitespace);
+        let line = self.line;
+        let column = self.column;
+        let letter = *self.chars.peek()?;
+
+        let token = match letter {
+            // Escaped lines, holding data for other programs.
+            '%' if column == 1 => {
+                self.skip_line();
+                return self.next_token();
+            }
+            '[' => {
+                self.bump();
+                self.tag_pair()
+            }
+            '{' => {
+                self.bump();
+                self.brace_comment()
+            }
+            ';' => {
+                self.bump();
+                Token::Comment(self.skip_line())
+            }
+            '(' => {
+                self.bump();
+                Token::StartVariation
+            }
+            ')' => {
+                self.bump();
+                Token::EndVariation
+            }
+            '$' => {
+                self.bump();
+                self.nag()
+            }
+            '*' => {
+                self.bump();
+                Token::Result(GameResult::Unknown)
+            }
+            // Dots left by "1. ... e5".
+            '.' => {
+                self.take_while(|letter| letter == '.');
+                Token::MoveNumber
+            }
+            '!' | '?' => {
+                let suffix = self.take_while(|letter| letter == '!' || letter == '?');
+                match suffix_nag(&suffix) {
+                    Some(nag) => Token::Nag(nag),
+                    None => Token::Invalid(format!("{} is not a glyph", suffix)),
+                }
+            }
+            letter if is_symbol_letter(letter) => self.symbol(),
+            letter => {
+                self.bump();
+                Token::Invalid(format!("unexpected character {}", letter))
+            }
+        };
+        Some(Located {
+            token,
+            line,
+            column,
+        })
+    }
+}
+
+/// Cuts the tokens into games: the tag pairs, then the moves up to the
+/// result. A game without result ends where the tags of the next one
+/// start.
+fn split_games(tokens: &[Located]) -> Vec<&[Located]> {
+    let mut res: Vec<&[Located]> = Vec::new();
+    let mut start = 0;
+    let mut in_movetext = false;
+    for (index, located) in tokens.iter().enumerate() {
+        match located.token {
+            Token::TagPair(..) if in_movetext => {
+                res.push(&tokens[start..index]);
+                start = index;
+                in_movetext = false;
+            }
+            Token::TagPair(..) | Token::Invalid(_) => {}
+            Token::Result(_) => {
+                res.push(&tokens[start..=index]);
+                start = index + 1;
+                in_movetext = false;
+            }
+            _ => in_movetext = true,
+        }
+    }
+    if start < tokens.len() {
+        res.push(&tokens[start..]);
+    }
+    res
+}
+
+/// The deepest nesting of variations read, so that a corrupt file can't
+/// exhaust the stack.
+const MAX_VARIATION_DEPTH: usize = 100;
+
+struct GameParser<'a> {
+    tokens: &'a [Located],
+    index: usize,
+    file: &'a str,
+    game: usize,
+    new_rules: RulesFactory,
+}
+
+impl<'a> GameParser<'a> {
+    fn error(&self, line: usize, column: usize, message: String) -> ParseError {
+        ParseError {
+            file: String::from(self.file),
+            game: self.game,
+            line,
+            column,
+            message,
+        }
+    }
+
+    /// An error at the current token, or at the last one of the game when
+    /// it ended too early.
+    fn error_here(&self, message: String) -> ParseError {
+        match self.tokens.get(self.index).or_else(|| self.tokens.last()) {
+            Some(located) => self.error(located.line, located.column, message),
+            None => self.error(0, 0, message),
+        }
+    }
+
+    fn parse_game(&mut self) -> Result<Game, ParseError> {
+        let tokens = self.tokens;
+        let mut tags: Vec<(String, String)> = Vec::new();
+        while let Some(Located {
+            token: Token::TagPair(name, value),
+            ..
+        }) = tokens.get(self.index)
+        {
+            tags.push((name.clone(), value.clone()));
+            self.index += 1;
+        }
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(tag_name, _)| tag_name == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let variant = match tag("Variant") {
+            Some(value) => Variant::from_tag(value)
+                .ok_or_else(|| self.error_here(format!("variant {} is not supported", value)))?,
+            None => Variant::Standard,
+        };
+        let start_position = match tag("FEN") {
+            Some(fen) if tag("SetUp") != Some("0") => PositionSetup::from_fen(fen)
+                .and_then(|setup| setup.validate().map_err(|e| e.to_string()))
+                .map_err(|e| self.error_here(format!("wrong FEN tag : {}", e)))?,
+            _ => String::from(START_POSITION),
+        };
+        let rules = (self.new_rules)(&start_position)
+            .map_err(|e| self.error_here(format!("wrong FEN tag : {}", e)))?;
+
+        let (comment, moves) = self.parse_line(rules, 0)?;
+        let result = match tokens.get(self.index) {
+            Some(Located {
+                token: Token::Result(result),
+                ..
+            }) => *result,
+            // The result is missing at the end of the file.
+            _ => tag("Result")
+                .and_then(GameResult::from_text)
+                .unwrap_or(GameResult::Unknown),
+        };
+
+        let mut game = Game {
+            tags,
+            variant,
+            start_position,
+            comment,
+            moves,
+            result,
+        };
+        game.tags = game.ordered_tags();
+        Ok(game)
+    }
+
+    /// Reads moves until the end of the variation, given by its closing
+    /// parenthesis, or of the game. Gives the comment before the first move
+    /// apart.
+    fn parse_line(
+        &mut self,
+        rules: Box<dyn ChessRules>,
+        depth: usize,
+    ) -> Result<(Option<Comment>, Vec<MoveNode>), ParseError> {
+        let tokens = self.tokens;
+        let mut position = rules;
+        let mut previous_position: Option<Box<dyn ChessRules>> = None;
+        let mut starting_comment: Option<Comment> = None;
+        let mut nodes: Vec<MoveNode> = Vec::new();
+
+        loop {
+            let located = match tokens.get(self.index) {
+                Some(located) => located,
+                None if depth > 0 => {
+                    return Err(self.error_here(String::from("unterminated variation")))
+                }
+                None => break,
+            };
+            match &located.token {
+                Token::Result(_) if depth > 0 => {
+                    return Err(self.error_here(String::from("result inside a variation")))
+                }
+                Token::Result(_) => break,
+                Token::EndVariation if depth == 0 => {
+                    return Err(self.error_here(String::from("no variation to close")))
+                }
+                Token::EndVariation => {
+                    self.index += 1;
+                    break;
+                }
+                Token::StartVariation => {
+                    let (last_node, previous_position) =
+                        match (nodes.last_mut(), previous_position.as_ref()) {
+                            (Some(last_node), Some(previous_position)) => {
+                                (last_node, previous_position.clone())
+                            }
+                            _ => {
+                                return Err(
+                                    self.error_here(String::from("variation before any move"))
+                                )
+                            }
+                        };
+                    if depth >= MAX_VARIATION_DEPTH {
+                        return Err(self.error_here(format!(
+                            "variations nested deeper than {}",
+                            MAX_VARIATION_DEPTH
+                        )));
+                    }
+                    let start = self.index;
+                    self.index += 1;
+                    let (comment, mut variation) = self.parse_line(previous_position, depth + 1)?;
+                    match variation.first_mut() {
+                        Some(first_node) => first_node.starting_comment = comment,
+                        None => {
+                            let start = &tokens[start];
+                            return Err(self.error(
+                                start.line,
+                                start.column,
+                                String::from("empty variation"),
+                            ));
+                        }
+                    }
+                    last_node.variations.push(variation);
+                    continue;
+                }
+                Token::Comment(text) => {
+                    let comment = Comment::parse(text);
+                    let target = match nodes.last_mut() {
+                        Some(last_node) => &mut last_node.comment,
+                        None => &mut starting_comment,
+                    };
+                    match target {
+                        Some(existing) => existing.append(comment),
+                        None => *target = Some(comment),
+                    }
+                }
+                Token::Nag(nag) => match nodes.last_mut() {
+                    Some(last_node) => last_node.nags.push(*nag),
+                    None => return Err(self.error_here(format!("glyph ${} before any move", nag))),
+                },
+                Token::MoveNumber => {}
+                Token::San(text) => {
+                    let chess_move = position
+                        .parse_san(text)
+                        .ok_or_else(|| self.error_here(format!("illegal move {}", text)))?;
+                    let san = position.san(&chess_move).unwrap_or_else(|| text.clone());
+                    previous_position = Some(position.clone());
+                    position.play(&chess_move);
+                    nodes.push(MoveNode::new(chess_move, san));
+                }
+                Token::TagPair(name, _) => {
+                    return Err(self.error_here(format!("tag {} among the moves", name)))
+                }
+                Token::Invalid(message) => return Err(self.error_here(message.clone())),
+            }
+            self.index += 1;
+        }
+
+        Ok((starting_comment, nodes))
+    }
+}
+
+/// Reads every game of the text, with the rules built by `new_rules`. A
+/// game with an error is left out and its error is reported, the following
+/// games being read anyway. The file name is only used in the errors.
+pub fn parse(file: &str, text: &str, new_rules: RulesFactory) -> PgnFile {
+    let mut lexer = Lexer::new(text);
+    let mut tokens: Vec<Located> = Vec::new();
+    while let Some(located) = lexer.next_token() {
+        tokens.push(located);
+    }
+
+    let mut res = PgnFile::default();
+    for (index, game_tokens) in split_games(&tokens).into_iter().enumerate() {
+        let mut parser = GameParser {
+            tokens: game_tokens,
+            index: 0,
+            file,
+            game: index + 1,
+            new_rules,
+        };
+        match parser.parse_game() {
+            Ok(game) => res.games.push(game),
+            Err(e) => res.errors.push(e),
+        }
+    }
+    res
+}
+
+pub fn read_file(path: &Path, new_rules: RulesFactory) -> Result<PgnFile, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read {} : {}", path.display(), e))?;
+    Ok(parse(&path.display().to_string(), &text, new_rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotation::{Annotation, AnnotationColor};
+    use crate::rules::pleco_rules;
+
+    use std::time::Duration;
+
+    const GAMES: &str = r#"[Event "Casual game"]
+[Site "?"]
+[Date "2021.03.04"]
+[Round "-"]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+[Result "1-0"]
+[Annotator "Nobody"]
+
+{Played in London.} 1. e4 e5 2. f4!? (2. Nf3 Nc6 (2... d6 {Philidor}) 3. Bb5)
+2... exf4 $6 3. Bc4 {[%clk 0:02:59.5] [%cal Gd1h5]} Qh4+ 4. Kf1 ; Losing castling
+b5 1-0
+
+[Event "Broken"]
+[Result "*"]
+
+1. e4 e5 2. Ke3 *
+
+[Variant "Chess960"]
+[SetUp "1"]
+[FEN "1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1"]
+[Result "*"]
+
+1. O-O-O O-O *
+"#;
+
+    #[test]
+    fn reads_games_and_skips_wrong_ones() {
+        let file = parse("games.pgn", GAMES, pleco_rules);
+        assert_eq!(file.games.len(), 2);
+        assert_eq!(
+            file.errors,
+            vec![ParseError {
+                file: String::from("games.pgn"),
+                game: 2,
+                line: 17,
+                column: 13,
+                message: String::from("illegal move Ke3"),
+            }]
+        );
+
+        let game = &file.games[0];
+        assert_eq!(game.tag("Annotator"), Some("Nobody"));
+        assert_eq!(game.result, GameResult::WhiteWins);
+        assert_eq!(game.comment.as_ref().unwrap().text, "Played in London.");
+        let sans: Vec<&str> = game.moves.iter().map(|node| node.san.as_str()).collect();
+        assert_eq!(
+            sans,
+            vec!["e4", "e5", "f4", "exf4", "Bc4", "Qh4+", "Kf1", "b5"]
+        );
+
+        let gambit = &game.moves[2];
+        assert_eq!(gambit.nags, vec![5]);
+        assert_eq!(gambit.variations.len(), 1);
+        let variation = &gambit.variations[0];
+        assert_eq!(variation.len(), 3);
+        let philidor = &variation[1].variations[0];
+        assert_eq!(philidor[0].san, "d6");
+        assert_eq!(philidor[0].comment.as_ref().unwrap().text, "Philidor");
+
+        assert_eq!(game.moves[3].nags, vec![6]);
+        let comment = game.moves[4].comment.as_ref().unwrap();
+        assert_eq!(comment.text, "");
+        assert_eq!(comment.clock, Some(Duration::from_millis(179_500)));
+        assert_eq!(
+            comment.annotations,
+            vec![Annotation::Arrow {
+                from: [3, 0],
+                to: [7, 4],
+                color: AnnotationColor::Green,
+            }]
+        );
+        assert_eq!(
+            game.moves[6].comment.as_ref().unwrap().text,
+            "Losing castling"
+        );
+    }
+
+    #[test]
+    fn reads_chess960_games() {
+        let file = parse("games.pgn", GAMES, pleco_rules);
+        let game = &file.games[1];
+        assert_eq!(game.variant, Variant::Chess960);
+        assert!(game.moves[0].chess_move.is_castling());
+        assert_eq!(game.moves[0].san, "O-O-O");
+        assert_eq!(game.moves[1].san, "O-O");
+
+        let mut rules = game.start_rules(pleco_rules);
+        for node in &game.moves {
+            assert!(rules.play(&node.chess_move));
+        }
+        assert_eq!(rules.fen(), "1r3rk1/8/8/8/8/8/8/2KR3R w - - 2 2");
+    }
+
+    #[test]
+    fn reports_unclosed_variations() {
+        let file = parse("open.pgn", "1. e4 (1. d4 d5 2. c4\n*", pleco_rules);
+        assert!(file.games.is_empty());
+        assert_eq!(file.errors[0].message, "result inside a variation");
+        assert_eq!((file.errors[0].line, file.errors[0].column), (2, 1));
+
+        let nested = format!("1. e4 {}*", "(1. d4 ".repeat(MAX_VARIATION_DEPTH + 1));
+        let file = parse("nested.pgn", &nested, pleco_rules);
+        assert!(file.games.is_empty());
+        assert_eq!(file.errors[0].message, "variations nested deeper than 100");
+    }
+}
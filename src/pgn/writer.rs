@@ -0,0 +1,187 @@
+//! Writes games in the PGN export format: tags in their standard order, one
+//! per line, then the moves wrapped at 80 columns.
+
+use super::{Comment, Game, MoveNode};
+use crate::position_setup::PositionSetup;
+
+const LINE_WIDTH: usize = 80;
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Adds the comment word by word, so that lines can be wrapped inside it.
+/// A comment holding a closing brace, read from a line comment, is written
+/// back as a line comment in a single word.
+fn push_comment(comment: &Comment, words: &mut Vec<String>) {
+    let text = comment.pgn_text();
+    if text.contains('}') {
+        words.push(format!("; {}", text));
+        return;
+    }
+    let mut comment_words: Vec<String> = text.split_whitespace().map(String::from).collect();
+    if comment_words.is_empty() {
+        words.push(String::from("{}"));
+        return;
+    }
+    comment_words[0].insert(0, '{');
+    if let Some(last) = comment_words.last_mut() {
+        last.push('}');
+    }
+    words.extend(comment_words);
+}
+
+/// Adds the moves of a line, the first one being played at the given ply.
+/// Black moves get their number at the start of the line and after
+/// comments and variations.
+fn push_line(nodes: &[MoveNode], first_ply: u64, words: &mut Vec<String>) {
+    let mut needs_number = true;
+    for (ply, node) in (first_ply..).zip(nodes) {
+        if let Some(ref comment) = node.starting_comment {
+            push_comment(comment, words);
+        }
+        let number = ply / 2 + 1;
+        if ply % 2 == 0 {
+            words.push(format!("{}.", number));
+        } else if needs_number {
+            words.push(format!("{}...", number));
+        }
+        words.push(node.san.clone());
+        for nag in &node.nags {
+            words.push(format!("${}", nag));
+        }
+        needs_number = false;
+
+        if let Some(ref comment) = node.comment {
+            push_comment(comment, words);
+            needs_number = true;
+        }
+        for variation in &node.variations {
+            let mut variation_words: Vec<String> = Vec::new();
+            push_line(variation, ply, &mut variation_words);
+            if let Some(first) = variation_words.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_words.last_mut() {
+                last.push(')');
+            }
+            words.extend(variation_words);
+            needs_number = true;
+        }
+    }
+}
+
+/// Joins the words with spaces, going to the next line before a word which
+/// would not fit and after a line comment. A word never starts a line with
+/// '%', which would escape the line.
+fn wrap(words: &[String]) -> String {
+    let mut res = String::new();
+    let mut line_width = 0;
+    let mut after_line_comment = false;
+    for word in words {
+        let width = word.chars().count();
+        let fits = line_width == 0 || line_width + 1 + width <= LINE_WIDTH;
+        if after_line_comment || (!fits && !word.starts_with('%')) {
+            res.push('\n');
+            line_width = 0;
+        } else if line_width > 0 {
+            res.push(' ');
+            line_width += 1;
+        }
+        res.push_str(word);
+        line_width += width;
+        after_line_comment = word.starts_with(';');
+    }
+    res
+}
+
+pub fn write_game(game: &Game) -> String {
+    let mut res = String::new();
+    for (name, value) in game.ordered_tags() {
+        res.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(&value)));
+    }
+    res.push('\n');
+
+    let first_ply = PositionSetup::from_fen(&game.start_position)
+        .map(|setup| {
+            let black_turn = if setup.white_turn { 0 } else { 1 };
+            // Counted in u64, as any u32 fullmove number is accepted.
+            u64::from(setup.fullmove_number.max(1) - 1) * 2 + black_turn
+        })
+        .unwrap_or(0);
+    let mut words: Vec<String> = Vec::new();
+    if let Some(ref comment) = game.comment {
+        push_comment(comment, &mut words);
+    }
+    push_line(&game.moves, first_ply, &mut words);
+    words.push(String::from(game.result.text()));
+    res.push_str(&wrap(&words));
+    res.push('\n');
+    res
+}
+
+/// The games of a file, separated by empty lines.
+#[cfg(test)]
+pub fn write_games(games: &[Game]) -> String {
+    games.iter().map(write_game).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse;
+    use crate::rules::pleco_rules;
+
+    const FILES: [(&str, &str); 3] = [
+        ("opera_game.pgn", include_str!("testdata/opera_game.pgn")),
+        (
+            "lichess_games.pgn",
+            include_str!("testdata/lichess_games.pgn"),
+        ),
+        ("exercises.pgn", include_str!("testdata/exercises.pgn")),
+    ];
+
+    #[test]
+    fn round_trips_sample_files() {
+        for (name, text) in FILES.iter() {
+            let file = parse(name, text, pleco_rules);
+            assert!(file.errors.is_empty(), "{:?}", file.errors);
+            assert!(!file.games.is_empty());
+
+            let written = write_games(&file.games);
+            assert!(written
+                .lines()
+                .all(|line| line.chars().count() <= LINE_WIDTH));
+            let reread = parse(name, &written, pleco_rules);
+            assert!(reread.errors.is_empty(), "{:?}", reread.errors);
+            assert_eq!(reread.games, file.games);
+            assert_eq!(write_games(&reread.games), written);
+        }
+    }
+
+    #[test]
+    fn numbers_moves_after_variations_and_black_starts() {
+        let text = "[FEN \"4kb1r/p2B1ppp/4qn2/4p1B1/4P3/1Q6/PPP2PPP/2KR4 b k - 0 15\"]\n\
+                    [Result \"1-0\"]\n\
+                    15... Nxd7 (15... Qxd7 16. Qb8+) 16. Qb8+ $3 {Mate follows.} Nxb8 17. Rd8# 1-0";
+        let file = parse("exercise.pgn", text, pleco_rules);
+        assert_eq!(
+            write_games(&file.games),
+            "[Result \"1-0\"]\n\
+             [SetUp \"1\"]\n\
+             [FEN \"4kb1r/p2B1ppp/4qn2/4p1B1/4P3/1Q6/PPP2PPP/2KR4 b k - 0 15\"]\n\
+             \n\
+             15... Nxd7 (15... Qxd7 16. Qb8+) 16. Qb8+ $3 {Mate follows.} 16... Nxb8 17. Rd8#\n\
+             1-0\n"
+        );
+    }
+
+    #[test]
+    fn numbers_moves_of_huge_fullmove_numbers() {
+        let text = "[FEN \"4k3/8/8/8/8/8/8/4K3 b - - 0 1\"]\n\n1... Kd7 2. Kd2 *";
+        let mut game = parse("huge.pgn", text, pleco_rules).games.remove(0);
+        // The rules reject such positions, but games can be built with them.
+        game.start_position = String::from("4k3/8/8/8/8/8/8/4K3 b - - 0 4294967295");
+        assert!(write_game(&game).contains("4294967295... Kd7 4294967296. Kd2 *"));
+    }
+}
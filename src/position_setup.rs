@@ -0,0 +1,489 @@
+//! Positions set up by hand, which unlike the positions of the rules may be
+//! illegal while the pieces are still being placed.
+
+use crate::annotation::{cell_text, parse_cell};
+use crate::rules::{ChessRules, Piece, PieceKind, PlecoRules, Side};
+
+pub const START_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// One of the four castling rights of a FEN string. In Chess960 positions
+/// the king and the rook may start on any file of the back rank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastlingRight {
+    WhiteShort,
+    WhiteLong,
+    BlackShort,
+    BlackLong,
+}
+
+impl CastlingRight {
+    /// In the FEN order.
+    pub const ALL: [CastlingRight; 4] = [
+        CastlingRight::WhiteShort,
+        CastlingRight::WhiteLong,
+        CastlingRight::BlackShort,
+        CastlingRight::BlackLong,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            CastlingRight::WhiteShort => 0,
+            CastlingRight::WhiteLong => 1,
+            CastlingRight::BlackShort => 2,
+            CastlingRight::BlackLong => 3,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            CastlingRight::WhiteShort => 'K',
+            CastlingRight::WhiteLong => 'Q',
+            CastlingRight::BlackShort => 'k',
+            CastlingRight::BlackLong => 'q',
+        }
+    }
+
+    pub fn of(side: Side, short: bool) -> Self {
+        match (side, short) {
+            (Side::White, true) => CastlingRight::WhiteShort,
+            (Side::White, false) => CastlingRight::WhiteLong,
+            (Side::Black, true) => CastlingRight::BlackShort,
+            (Side::Black, false) => CastlingRight::BlackLong,
+        }
+    }
+
+    pub fn side(self) -> Side {
+        match self {
+            CastlingRight::WhiteShort | CastlingRight::WhiteLong => Side::White,
+            CastlingRight::BlackShort | CastlingRight::BlackLong => Side::Black,
+        }
+    }
+
+    /// Towards the h file.
+    pub fn is_short(self) -> bool {
+        matches!(self, CastlingRight::WhiteShort | CastlingRight::BlackShort)
+    }
+
+    /// The back rank of the side.
+    pub fn rank(self) -> u8 {
+        match self.side() {
+            Side::White => 0,
+            Side::Black => 7,
+        }
+    }
+
+    /// The files of the king and of the rook once castled.
+    pub fn target_files(self) -> (u8, u8) {
+        if self.is_short() {
+            (6, 5)
+        } else {
+            (2, 3)
+        }
+    }
+
+    /// The file of the rook in standard chess.
+    fn standard_rook_file(self) -> u8 {
+        if self.is_short() {
+            7
+        } else {
+            0
+        }
+    }
+
+    fn rook(self) -> Piece {
+        Piece::new(self.side(), PieceKind::Rook)
+    }
+}
+
+/// Why a set up position can't be played from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetupError {
+    WrongKingCount,
+    PawnOnBackRank,
+    OpponentInCheck,
+    WrongCastlingRights,
+    WrongEnPassant,
+    WrongFullmoveNumber,
+    Rejected(String),
+}
+
+impl std::fmt::Display for SetupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetupError::WrongKingCount => write!(f, "each side needs exactly one king"),
+            SetupError::PawnOnBackRank => write!(f, "pawns can't stand on the first or last rank"),
+            SetupError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            SetupError::WrongCastlingRights => {
+                write!(f, "castling rights don't match the king and rook positions")
+            }
+            SetupError::WrongEnPassant => write!(f, "no pawn can be taken en passant there"),
+            SetupError::WrongFullmoveNumber => write!(f, "the fullmove number starts at 1"),
+            SetupError::Rejected(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// A position being edited. Cells are given as [file, rank].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionSetup {
+    pieces: [Option<Piece>; 64],
+    pub white_turn: bool,
+    /// The file of the rook of each castling right.
+    castling_rooks: [Option<u8>; 4],
+    pub en_passant: Option<[u8; 2]>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+impl Default for PositionSetup {
+    fn default() -> Self {
+        Self::start()
+    }
+}
+
+impl PositionSetup {
+    /// An empty board, white to move.
+    pub fn empty() -> Self {
+        Self {
+            pieces: [None; 64],
+            white_turn: true,
+            castling_rooks: [None; 4],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    pub fn start() -> Self {
+        Self::from_fen(START_POSITION).unwrap_or_else(|_| Self::empty())
+    }
+
+    /// Reads a FEN string without checking that the position is legal. The
+    /// move counters may be missing. Castling rights may also be given as
+    /// the files of the rooks, as in X-FEN and Shredder-FEN strings.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+        if parts.len() < 4 || parts.len() > 6 {
+            return Err(format!("{} should have from 4 to 6 fields", fen));
+        }
+
+        let mut res = Self::empty();
+
+        let ranks: Vec<&str> = parts[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!("{} should have 8 ranks", parts[0]));
+        }
+        for (row, rank_text) in ranks.iter().enumerate() {
+            let rank = 7 - row as u8;
+            let mut file = 0u8;
+            for letter in rank_text.chars() {
+                if let Some(empty_cells) = letter.to_digit(10) {
+                    file += empty_cells as u8;
+                } else {
+                    let piece = Piece::from_letter(letter)
+                        .ok_or_else(|| format!("{} is not a piece", letter))?;
+                    if file < 8 {
+                        res.set_piece([file, rank], Some(piece));
+                    }
+                    file += 1;
+                }
+                if file > 8 {
+                    return Err(format!("rank {} is too long", rank_text));
+                }
+            }
+            if file != 8 {
+                return Err(format!("rank {} is too short", rank_text));
+            }
+        }
+
+        res.white_turn = match parts[1] {
+            "w" => true,
+            "b" => false,
+            turn => return Err(format!("{} is not a side to move", turn)),
+        };
+
+        if parts[2] != "-" {
+            for letter in parts[2].chars() {
+                res.add_castling_letter(letter)?;
+            }
+        }
+
+        res.en_passant = match parts[3] {
+            "-" => None,
+            text => Some(parse_cell(text).ok_or_else(|| format!("{} is not a cell", text))?),
+        };
+
+        if let Some(text) = parts.get(4) {
+            res.halfmove_clock = text
+                .parse()
+                .map_err(|_| format!("{} is not a halfmove clock", text))?;
+        }
+        if let Some(text) = parts.get(5) {
+            res.fullmove_number = text
+                .parse()
+                .map_err(|_| format!("{} is not a fullmove number", text))?;
+        }
+
+        Ok(res)
+    }
+
+    pub fn piece_at(&self, cell: [u8; 2]) -> Option<Piece> {
+        self.pieces[(cell[0] + 8 * cell[1]) as usize]
+    }
+
+    pub fn set_piece(&mut self, cell: [u8; 2], piece: Option<Piece>) {
+        self.pieces[(cell[0] + 8 * cell[1]) as usize] = piece;
+    }
+
+    /// The cell of the king of the side, the first one if there are
+    /// several.
+    pub fn king_cell(&self, side: Side) -> Option<[u8; 2]> {
+        let king = Some(Piece::new(side, PieceKind::King));
+        (0..64u8)
+            .map(|index| [index % 8, index / 8])
+            .find(|cell| self.piece_at(*cell) == king)
+    }
+
+    /// The file of the king when it is on the back rank of the right.
+    fn back_rank_king_file(&self, right: CastlingRight) -> Option<u8> {
+        self.king_cell(right.side())
+            .filter(|cell| cell[1] == right.rank())
+            .map(|cell| cell[0])
+    }
+
+    /// The file of the rook furthest from the king on the side of the
+    /// right, which is the rook the K, Q, k and q letters refer to.
+    fn outermost_rook_file(&self, right: CastlingRight) -> Option<u8> {
+        let king_file = self.back_rank_king_file(right)?;
+        let is_rook = |file: &u8| self.piece_at([*file, right.rank()]) == Some(right.rook());
+        if right.is_short() {
+            (king_file + 1..8).rev().find(is_rook)
+        } else {
+            (0..king_file).find(is_rook)
+        }
+    }
+
+    fn add_castling_letter(&mut self, letter: char) -> Result<(), String> {
+        if let Some(right) = CastlingRight::ALL
+            .iter()
+            .copied()
+            .find(|right| right.letter() == letter)
+        {
+            // Without a rook, the right is kept on the standard file and
+            // rejected when validating.
+            let file = self
+                .outermost_rook_file(right)
+                .unwrap_or_else(|| right.standard_rook_file());
+            self.castling_rooks[right.index()] = Some(file);
+            return Ok(());
+        }
+
+        let (side, file) = match letter {
+            'A'..='H' => (Side::White, letter as u8 - b'A'),
+            'a'..='h' => (Side::Black, letter as u8 - b'a'),
+            _ => return Err(format!("{} is not a castling right", letter)),
+        };
+        let king_file = self
+            .back_rank_king_file(CastlingRight::of(side, true))
+            .unwrap_or(4);
+        let right = CastlingRight::of(side, file > king_file);
+        self.castling_rooks[right.index()] = Some(file);
+        Ok(())
+    }
+
+    pub fn has_castling_right(&self, right: CastlingRight) -> bool {
+        self.castling_rooks[right.index()].is_some()
+    }
+
+    /// The file of the rook the king castles with.
+    pub fn castling_rook(&self, right: CastlingRight) -> Option<u8> {
+        self.castling_rooks[right.index()]
+    }
+
+    /// Gives the right with the outermost rook on its side, or the rook of
+    /// standard chess when there is none.
+    pub fn set_castling_right(&mut self, right: CastlingRight, allowed: bool) {
+        self.castling_rooks[right.index()] = if allowed {
+            Some(
+                self.outermost_rook_file(right)
+                    .unwrap_or_else(|| right.standard_rook_file()),
+            )
+        } else {
+            None
+        };
+    }
+
+    pub fn set_castling_rook(&mut self, right: CastlingRight, rook_file: Option<u8>) {
+        self.castling_rooks[right.index()] = rook_file;
+    }
+
+    /// Whether pleco can castle with the right, the king and the rook being
+    /// on their standard cells.
+    fn is_standard_castling(&self, right: CastlingRight) -> bool {
+        self.castling_rook(right) == Some(right.standard_rook_file())
+            && self.back_rank_king_file(right) == Some(4)
+    }
+
+    /// Whether a castling right can only be played with the rules of
+    /// Chess960, its king or its rook not being on its standard cell.
+    pub fn has_chess960_castling(&self) -> bool {
+        CastlingRight::ALL
+            .iter()
+            .any(|right| self.has_castling_right(*right) && !self.is_standard_castling(*right))
+    }
+
+    fn placement_fen(&self) -> String {
+        let mut res = String::new();
+        for rank in (0..8).rev() {
+            let mut empty_cells = 0;
+            for file in 0..8 {
+                match self.piece_at([file, rank]).map(Piece::letter) {
+                    Some(letter) => {
+                        if empty_cells > 0 {
+                            res.push_str(&empty_cells.to_string());
+                            empty_cells = 0;
+                        }
+                        res.push(letter);
+                    }
+                    None => empty_cells += 1,
+                }
+            }
+            if empty_cells > 0 {
+                res.push_str(&empty_cells.to_string());
+            }
+            if rank > 0 {
+                res.push('/');
+            }
+        }
+        res
+    }
+
+    /// The KQkq letters, or the file of the rook as in X-FEN when another
+    /// rook stands further on the same side.
+    fn castling_fen(&self) -> String {
+        let res: String = CastlingRight::ALL
+            .iter()
+            .filter_map(|right| {
+                let file = self.castling_rook(*right)?;
+                if self.outermost_rook_file(*right) == Some(file) {
+                    Some(right.letter())
+                } else if right.side() == Side::White {
+                    Some((b'A' + file) as char)
+                } else {
+                    Some((b'a' + file) as char)
+                }
+            })
+            .collect();
+        if res.is_empty() {
+            String::from("-")
+        } else {
+            res
+        }
+    }
+
+    /// The FEN string of the setup, legal or not.
+    pub fn fen(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.placement_fen(),
+            if self.white_turn { "w" } else { "b" },
+            self.castling_fen(),
+            self.en_passant
+                .map(cell_text)
+                .unwrap_or_else(|| String::from("-")),
+            self.halfmove_clock,
+            self.fullmove_number,
+        )
+    }
+
+    /// The FEN string of the setup without the castling rights pleco can't
+    /// play, those of Chess960 positions.
+    pub fn pleco_fen(&self) -> String {
+        let mut res = self.clone();
+        for right in CastlingRight::ALL.iter() {
+            if !self.is_standard_castling(*right) {
+                res.set_castling_rook(*right, None);
+            }
+        }
+        res.fen()
+    }
+
+    fn count(&self, piece: Piece) -> usize {
+        self.pieces.iter().filter(|p| **p == Some(piece)).count()
+    }
+
+    /// Whether the pawn which has just moved two cells could be taken on the
+    /// en passant cell.
+    fn is_en_passant_possible(&self, cell: [u8; 2]) -> bool {
+        // Ranks of the en passant cell, of the cell the pawn left and of the
+        // pawn itself.
+        let (rank, start_rank, pawn_rank, pawn) = if self.white_turn {
+            (5, 6, 4, Piece::BlackPawn)
+        } else {
+            (2, 1, 3, Piece::WhitePawn)
+        };
+        let [file, _] = cell;
+        cell[1] == rank
+            && self.piece_at(cell).is_none()
+            && self.piece_at([file, start_rank]).is_none()
+            && self.piece_at([file, pawn_rank]) == Some(pawn)
+    }
+
+    /// Checks that the position can be played from, and gives its FEN string.
+    pub fn validate(&self) -> Result<String, SetupError> {
+        if self.count(Piece::WhiteKing) != 1 || self.count(Piece::BlackKing) != 1 {
+            return Err(SetupError::WrongKingCount);
+        }
+
+        let pawn_on_back_rank = (0..8).any(|file| {
+            [0, 7]
+                .iter()
+                .any(|rank| self.piece_at([file, *rank]).map(Piece::kind) == Some(PieceKind::Pawn))
+        });
+        if pawn_on_back_rank {
+            return Err(SetupError::PawnOnBackRank);
+        }
+
+        let castling_mismatch = CastlingRight::ALL.iter().any(|right| {
+            let rook_file = match self.castling_rook(*right) {
+                Some(rook_file) => rook_file,
+                None => return false,
+            };
+            let king_file = match self.back_rank_king_file(*right) {
+                Some(king_file) => king_file,
+                None => return true,
+            };
+            self.piece_at([rook_file, right.rank()]) != Some(right.rook())
+                || right.is_short() != (rook_file > king_file)
+        });
+        if castling_mismatch {
+            return Err(SetupError::WrongCastlingRights);
+        }
+
+        if let Some(cell) = self.en_passant {
+            if !self.is_en_passant_possible(cell) {
+                return Err(SetupError::WrongEnPassant);
+            }
+        }
+
+        if self.fullmove_number == 0 {
+            return Err(SetupError::WrongFullmoveNumber);
+        }
+
+        // The side not to move being in check is the side to move being in
+        // check once the turn is swapped.
+        let mut swapped = self.clone();
+        swapped.white_turn = !self.white_turn;
+        swapped.en_passant = None;
+        if PlecoRules::from_fen(&swapped.fen())
+            .map_err(SetupError::Rejected)?
+            .is_check()
+        {
+            return Err(SetupError::OpponentInCheck);
+        }
+
+        let fen = self.fen();
+        PlecoRules::from_fen(&fen).map_err(SetupError::Rejected)?;
+        Ok(fen)
+    }
+}
@@ -0,0 +1,260 @@
+//! The rules of chess as the rest of the application sees them: the board
+//! widget, the PGN reader and the training logic only talk to
+//! `ChessRules`, built by the `RulesFactory` they are given, so that
+//! another move generator, or a test double, can replace pleco. Only
+//! `PositionSetup` still checks the positions it validates with pleco.
+
+mod pleco_rules;
+mod san;
+
+pub use pleco_rules::PlecoRules;
+
+use crate::annotation::cell_text;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    White,
+    Black,
+}
+
+impl Side {
+    pub fn opponent(self) -> Self {
+        match self {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
+        }
+    }
+
+    /// The side moving when the FEN turn field is "w" or not.
+    pub fn from_white_turn(white_turn: bool) -> Self {
+        if white_turn {
+            Side::White
+        } else {
+            Side::Black
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    /// The uppercase letter of SAN moves and of white pieces in FEN strings.
+    pub fn letter(self) -> char {
+        match self {
+            PieceKind::Pawn => 'P',
+            PieceKind::Knight => 'N',
+            PieceKind::Bishop => 'B',
+            PieceKind::Rook => 'R',
+            PieceKind::Queen => 'Q',
+            PieceKind::King => 'K',
+        }
+    }
+
+    /// Reads the letter in either case.
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter.to_ascii_uppercase() {
+            'P' => Some(PieceKind::Pawn),
+            'N' => Some(PieceKind::Knight),
+            'B' => Some(PieceKind::Bishop),
+            'R' => Some(PieceKind::Rook),
+            'Q' => Some(PieceKind::Queen),
+            'K' => Some(PieceKind::King),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Piece {
+    WhitePawn,
+    WhiteKnight,
+    WhiteBishop,
+    WhiteRook,
+    WhiteQueen,
+    WhiteKing,
+    BlackPawn,
+    BlackKnight,
+    BlackBishop,
+    BlackRook,
+    BlackQueen,
+    BlackKing,
+}
+
+impl Piece {
+    pub fn new(side: Side, kind: PieceKind) -> Self {
+        match (side, kind) {
+            (Side::White, PieceKind::Pawn) => Piece::WhitePawn,
+            (Side::White, PieceKind::Knight) => Piece::WhiteKnight,
+            (Side::White, PieceKind::Bishop) => Piece::WhiteBishop,
+            (Side::White, PieceKind::Rook) => Piece::WhiteRook,
+            (Side::White, PieceKind::Queen) => Piece::WhiteQueen,
+            (Side::White, PieceKind::King) => Piece::WhiteKing,
+            (Side::Black, PieceKind::Pawn) => Piece::BlackPawn,
+            (Side::Black, PieceKind::Knight) => Piece::BlackKnight,
+            (Side::Black, PieceKind::Bishop) => Piece::BlackBishop,
+            (Side::Black, PieceKind::Rook) => Piece::BlackRook,
+            (Side::Black, PieceKind::Queen) => Piece::BlackQueen,
+            (Side::Black, PieceKind::King) => Piece::BlackKing,
+        }
+    }
+
+    pub fn side(self) -> Side {
+        match self {
+            Piece::WhitePawn
+            | Piece::WhiteKnight
+            | Piece::WhiteBishop
+            | Piece::WhiteRook
+            | Piece::WhiteQueen
+            | Piece::WhiteKing => Side::White,
+            _ => Side::Black,
+        }
+    }
+
+    pub fn kind(self) -> PieceKind {
+        match self {
+            Piece::WhitePawn | Piece::BlackPawn => PieceKind::Pawn,
+            Piece::WhiteKnight | Piece::BlackKnight => PieceKind::Knight,
+            Piece::WhiteBishop | Piece::BlackBishop => PieceKind::Bishop,
+            Piece::WhiteRook | Piece::BlackRook => PieceKind::Rook,
+            Piece::WhiteQueen | Piece::BlackQueen => PieceKind::Queen,
+            Piece::WhiteKing | Piece::BlackKing => PieceKind::King,
+        }
+    }
+
+    /// The letter of the piece in FEN strings, uppercase for white.
+    pub fn letter(self) -> char {
+        match self.side() {
+            Side::White => self.kind().letter(),
+            Side::Black => self.kind().letter().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        let side = if letter.is_ascii_uppercase() {
+            Side::White
+        } else {
+            Side::Black
+        };
+        PieceKind::from_letter(letter).map(|kind| Piece::new(side, kind))
+    }
+}
+
+/// A move between two cells given as [file, rank].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub from: [u8; 2],
+    /// Where the king ends when castling.
+    pub to: [u8; 2],
+    pub promotion: Option<PieceKind>,
+    /// The cell of the rook the king castles with, which is where the king
+    /// is dropped to castle in Chess960 positions.
+    pub castling_rook: Option<[u8; 2]>,
+}
+
+impl Move {
+    pub fn new(from: [u8; 2], to: [u8; 2]) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+            castling_rook: None,
+        }
+    }
+
+    pub fn is_castling(&self) -> bool {
+        self.castling_rook.is_some()
+    }
+
+    /// Whether the move is the one of a piece dropped from one cell on the
+    /// other.
+    pub fn is_drop(&self, from: [u8; 2], to: [u8; 2]) -> bool {
+        self.from == from && (self.to == to || self.castling_rook == Some(to))
+    }
+
+    /// The move in UCI notation, e.g. "e7e8q".
+    pub fn uci(&self) -> String {
+        let mut res = format!("{}{}", cell_text(self.from), cell_text(self.to));
+        if let Some(promotion) = self.promotion {
+            res.push(promotion.letter().to_ascii_lowercase());
+        }
+        res
+    }
+}
+
+/// A position and the moves which can be played from it.
+pub trait ChessRules {
+    fn piece_at(&self, cell: [u8; 2]) -> Option<Piece>;
+
+    fn side_to_move(&self) -> Side;
+
+    fn legal_moves(&self) -> Vec<Move>;
+
+    /// The moves the pieces can make, even those leaving the king in check.
+    fn pseudo_legal_moves(&self) -> Vec<Move>;
+
+    fn is_check(&self) -> bool;
+
+    /// Plays the move if it is legal, and tells whether it was.
+    fn play(&mut self, chess_move: &Move) -> bool;
+
+    fn fen(&self) -> String;
+
+    fn clone_box(&self) -> Box<dyn ChessRules>;
+
+    fn is_checkmate(&self) -> bool {
+        self.is_check() && self.legal_moves().is_empty()
+    }
+
+    fn king_cell(&self, side: Side) -> Option<[u8; 2]> {
+        let king = Piece::new(side, PieceKind::King);
+        (0..64u8)
+            .map(|index| [index % 8, index / 8])
+            .find(|cell| self.piece_at(*cell) == Some(king))
+    }
+
+    fn is_capture(&self, chess_move: &Move) -> bool {
+        if chess_move.is_castling() {
+            return false;
+        }
+        let en_passant = self
+            .piece_at(chess_move.from)
+            .is_some_and(|piece| piece.kind() == PieceKind::Pawn)
+            && chess_move.from[0] != chess_move.to[0];
+        en_passant || self.piece_at(chess_move.to).is_some()
+    }
+
+    /// The move in standard algebraic notation, with the check and mate
+    /// suffixes, or None for an illegal move.
+    fn san(&self, chess_move: &Move) -> Option<String> {
+        san::move_to_san(self, chess_move)
+    }
+
+    /// The legal move written in standard algebraic notation. Annotation
+    /// symbols and check suffixes are ignored, "0-0" is read as "O-O".
+    fn parse_san(&self, text: &str) -> Option<Move> {
+        san::parse_san(self, text)
+    }
+}
+
+impl Clone for Box<dyn ChessRules> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Builds the rules of the position given as a FEN string. The PGN reader
+/// and the exercises are given one, so that they don't depend on a backend.
+pub type RulesFactory = fn(&str) -> Result<Box<dyn ChessRules>, String>;
+
+/// The `RulesFactory` of pleco.
+pub fn pleco_rules(fen: &str) -> Result<Box<dyn ChessRules>, String> {
+    PlecoRules::from_fen(fen).map(|rules| Box::new(rules) as Box<dyn ChessRules>)
+}
@@ -0,0 +1,376 @@
+//! The rules backed by pleco. pleco only knows the castling of standard
+//! chess, so castling is played here, which also covers Chess960 positions,
+//! while pleco plays the other moves.
+
+use pleco::core::{bitboard::BitBoard, piece_move::BitMove, sq::SQ, PieceType, Player};
+use pleco::Board;
+
+use super::{ChessRules, Move, Piece, PieceKind, Side};
+use crate::position_setup::{CastlingRight, PositionSetup};
+
+fn player_of(side: Side) -> Player {
+    match side {
+        Side::White => Player::White,
+        Side::Black => Player::Black,
+    }
+}
+
+fn square_of(cell: [u8; 2]) -> SQ {
+    SQ(cell[0] + 8 * cell[1])
+}
+
+fn cell_of(square: SQ) -> [u8; 2] {
+    [square.0 % 8, square.0 / 8]
+}
+
+fn kind_of(piece_type: PieceType) -> Option<PieceKind> {
+    match piece_type {
+        PieceType::P => Some(PieceKind::Pawn),
+        PieceType::N => Some(PieceKind::Knight),
+        PieceType::B => Some(PieceKind::Bishop),
+        PieceType::R => Some(PieceKind::Rook),
+        PieceType::Q => Some(PieceKind::Queen),
+        PieceType::K => Some(PieceKind::King),
+        _ => None,
+    }
+}
+
+fn move_of(bit_move: BitMove) -> Move {
+    Move {
+        from: cell_of(bit_move.get_src()),
+        to: cell_of(bit_move.get_dest()),
+        promotion: if bit_move.is_promo() {
+            kind_of(bit_move.promo_piece())
+        } else {
+            None
+        },
+        castling_rook: None,
+    }
+}
+
+fn rejected(e: pleco::board::FenBuildError) -> String {
+    format!("{:?}", e).trim().to_string()
+}
+
+/// The position once castled, or None when the king is in check, would
+/// cross an attacked cell, or when a piece stands in the way of the king or
+/// of the rook.
+fn castle(setup: &PositionSetup, right: CastlingRight) -> Option<PositionSetup> {
+    let rank = right.rank();
+    let rook_file = setup.castling_rook(right)?;
+    let king_file = setup
+        .king_cell(right.side())
+        .filter(|cell| cell[1] == rank)?[0];
+    let (king_target, rook_target) = right.target_files();
+
+    let files = [king_file, rook_file, king_target, rook_target];
+    let (low, high) = (*files.iter().min()?, *files.iter().max()?);
+    let blocked = (low..=high).any(|file| {
+        file != king_file && file != rook_file && setup.piece_at([file, rank]).is_some()
+    });
+    if blocked {
+        return None;
+    }
+
+    // The castling pieces are lifted so that they don't hide the cells the
+    // king crosses.
+    let board = Board::from_fen(&setup.pleco_fen()).ok()?;
+    let lifted = square_of([king_file, rank]).to_bb() | square_of([rook_file, rank]).to_bb();
+    let occupied = board.occupied() & !lifted;
+    let opponent_pieces = board.get_occupied_player(player_of(right.side().opponent()));
+    let is_attacked = |file: u8| {
+        let attackers: BitBoard = board.attackers_to(square_of([file, rank]), occupied);
+        (attackers & opponent_pieces).is_not_empty()
+    };
+    if (king_file.min(king_target)..=king_file.max(king_target)).any(is_attacked) {
+        return None;
+    }
+
+    let mut res = setup.clone();
+    res.set_piece([king_file, rank], None);
+    res.set_piece([rook_file, rank], None);
+    res.set_piece(
+        [king_target, rank],
+        Some(Piece::new(right.side(), PieceKind::King)),
+    );
+    res.set_piece(
+        [rook_target, rank],
+        Some(Piece::new(right.side(), PieceKind::Rook)),
+    );
+    for other_right in CastlingRight::ALL.iter() {
+        if other_right.side() == right.side() {
+            res.set_castling_rook(*other_right, None);
+        }
+    }
+    res.en_passant = None;
+    res.halfmove_clock += 1;
+    if !setup.white_turn {
+        res.fullmove_number += 1;
+    }
+    res.white_turn = !setup.white_turn;
+    Some(res)
+}
+
+/// The position after a move played by pleco, whose FEN string lacks the
+/// Chess960 castling rights: the rights are those of the position before
+/// the move, but for the king or rook which moved or was taken.
+fn after_move(
+    before: &PositionSetup,
+    after_fen: &str,
+    chess_move: &Move,
+) -> Result<PositionSetup, String> {
+    let mut res = PositionSetup::from_fen(after_fen)?;
+    for right in CastlingRight::ALL.iter() {
+        let king_moved = before.king_cell(right.side()) == Some(chess_move.from);
+        let rook_file = before.castling_rook(*right).filter(|file| {
+            let rook_cell = [*file, right.rank()];
+            !king_moved && rook_cell != chess_move.from && rook_cell != chess_move.to
+        });
+        res.set_castling_rook(*right, rook_file);
+    }
+    Ok(res)
+}
+
+/// A pleco board, with the castling rights it can't hold kept aside.
+#[derive(Clone)]
+pub struct PlecoRules {
+    board: Board,
+    position: PositionSetup,
+}
+
+impl PlecoRules {
+    /// Reads a FEN string, whose castling rights may be given as in X-FEN
+    /// or Shredder-FEN strings.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let position = PositionSetup::from_fen(fen)?;
+        let board = Board::from_fen(&position.pleco_fen()).map_err(rejected)?;
+        Ok(Self { board, position })
+    }
+
+    #[cfg(test)]
+    pub fn start() -> Self {
+        Self {
+            board: Board::start_pos(),
+            position: PositionSetup::start(),
+        }
+    }
+
+    fn castling_moves(&self) -> Vec<Move> {
+        let side = self.side_to_move();
+        let king_cell = match self.position.king_cell(side) {
+            Some(king_cell) => king_cell,
+            None => return Vec::new(),
+        };
+        CastlingRight::ALL
+            .iter()
+            .filter(|right| right.side() == side)
+            .filter_map(|right| {
+                let rook_file = self.position.castling_rook(*right)?;
+                castle(&self.position, *right)?;
+                let (king_target, _) = right.target_files();
+                Some(Move {
+                    from: king_cell,
+                    to: [king_target, right.rank()],
+                    promotion: None,
+                    castling_rook: Some([rook_file, right.rank()]),
+                })
+            })
+            .collect()
+    }
+
+    fn play_castling(&mut self, rook_cell: [u8; 2]) -> bool {
+        let side = self.side_to_move();
+        let right = CastlingRight::ALL.iter().copied().find(|right| {
+            right.side() == side
+                && right.rank() == rook_cell[1]
+                && self.position.castling_rook(*right) == Some(rook_cell[0])
+        });
+        let castled = right.and_then(|right| castle(&self.position, right));
+        let board = castled
+            .as_ref()
+            .and_then(|castled| Board::from_fen(&castled.pleco_fen()).ok());
+        match (castled, board) {
+            (Some(castled), Some(board)) => {
+                self.board = board;
+                self.position = castled;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ChessRules for PlecoRules {
+    fn piece_at(&self, cell: [u8; 2]) -> Option<Piece> {
+        self.position.piece_at(cell)
+    }
+
+    fn side_to_move(&self) -> Side {
+        Side::from_white_turn(self.position.white_turn)
+    }
+
+    fn legal_moves(&self) -> Vec<Move> {
+        let mut res: Vec<Move> = self
+            .board
+            .generate_moves()
+            .iter()
+            .filter(|bit_move| !bit_move.is_castle())
+            .map(|bit_move| move_of(*bit_move))
+            .collect();
+        res.extend(self.castling_moves());
+        res
+    }
+
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut res: Vec<Move> = self
+            .board
+            .generate_pseudolegal_moves()
+            .iter()
+            .filter(|bit_move| !bit_move.is_castle())
+            .map(|bit_move| move_of(*bit_move))
+            .collect();
+        res.extend(self.castling_moves());
+        res
+    }
+
+    fn is_check(&self) -> bool {
+        self.board.in_check()
+    }
+
+    fn play(&mut self, chess_move: &Move) -> bool {
+        if let Some(rook_cell) = chess_move.castling_rook {
+            return self.castling_moves().contains(chess_move) && self.play_castling(rook_cell);
+        }
+
+        let bit_move = self
+            .board
+            .generate_moves()
+            .iter()
+            .find(|bit_move| !bit_move.is_castle() && move_of(**bit_move) == *chess_move)
+            .copied();
+        let bit_move = match bit_move {
+            Some(bit_move) => bit_move,
+            None => return false,
+        };
+        self.board.apply_move(bit_move);
+        match after_move(&self.position, &self.board.fen(), chess_move) {
+            Ok(position) => {
+                self.position = position;
+                true
+            }
+            // The board and the position must stay the same position.
+            Err(_) => {
+                self.board.undo_move();
+                false
+            }
+        }
+    }
+
+    fn fen(&self) -> String {
+        self.position.fen()
+    }
+
+    fn clone_box(&self) -> Box<dyn ChessRules> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_san(rules: &mut PlecoRules, moves: &[&str]) {
+        for san in moves {
+            let chess_move = rules.parse_san(san).unwrap();
+            assert_eq!(rules.san(&chess_move).as_deref(), Some(*san));
+            assert!(rules.play(&chess_move));
+        }
+    }
+
+    #[test]
+    fn writes_and_reads_san() {
+        let mut rules = PlecoRules::start();
+        play_san(
+            &mut rules,
+            &[
+                "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "f6",
+            ],
+        );
+        assert_eq!(
+            rules.fen(),
+            "r1bqkbnr/1pp3pp/p1p2p2/4p3/4P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 0 6"
+        );
+
+        let mut rules = PlecoRules::from_fen("4k3/P7/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        play_san(&mut rules, &["a8=Q+"]);
+
+        let rules = PlecoRules::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let chess_move = rules.parse_san("Rd1").unwrap();
+        assert_eq!(chess_move.from, [0, 0]);
+        assert!(rules.parse_san("Rf1").is_some());
+        assert!(rules.parse_san("Rb2").is_none());
+
+        let mut rules = PlecoRules::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        play_san(&mut rules, &["Ra7", "Kf8"]);
+        let chess_move = rules.parse_san("Rh8#").unwrap();
+        assert_eq!(rules.san(&chess_move).as_deref(), Some("Rh8#"));
+    }
+
+    #[test]
+    fn disambiguates_san() {
+        let rules = PlecoRules::from_fen("4k3/8/8/8/8/8/4K3/R6R w - - 0 1").unwrap();
+        let chess_move = Move::new([0, 0], [3, 0]);
+        assert_eq!(rules.san(&chess_move).as_deref(), Some("Rad1"));
+        assert!(rules.parse_san("Rd1").is_none());
+        assert_eq!(rules.parse_san("Rad1"), Some(chess_move));
+
+        let rules = PlecoRules::from_fen("4k3/8/8/N7/8/8/8/N3K3 w - - 0 1").unwrap();
+        let chess_move = Move::new([0, 0], [1, 2]);
+        assert_eq!(rules.san(&chess_move).as_deref(), Some("N1b3"));
+    }
+
+    #[test]
+    fn castles_chess960_positions_by_dropping_the_king_on_the_rook() {
+        let fen = "rk2r3/pppppppp/8/8/8/8/PPPPPPPP/RK2R3 w KQkq - 0 1";
+        let mut rules = PlecoRules::from_fen(fen).unwrap();
+        let short = rules
+            .legal_moves()
+            .into_iter()
+            .find(|chess_move| chess_move.is_drop([1, 0], [4, 0]))
+            .unwrap();
+        assert_eq!(rules.san(&short).as_deref(), Some("O-O"));
+        assert!(rules.play(&short));
+        assert_eq!(
+            rules.fen(),
+            "rk2r3/pppppppp/8/8/8/8/PPPPPPPP/R4RK1 b kq - 1 1"
+        );
+
+        // The rook on a1 is next to the king, but c1 must be free.
+        let rules = PlecoRules::from_fen(fen).unwrap();
+        assert!(rules.parse_san("O-O-O").is_some());
+        let fen = "rk2r3/pppppppp/8/8/8/8/PPPPPPPP/RKB1R3 w KQkq - 0 1";
+        let rules = PlecoRules::from_fen(fen).unwrap();
+        assert!(rules.parse_san("O-O-O").is_none());
+    }
+
+    #[test]
+    fn refuses_castling_through_attacked_cells() {
+        let rules = PlecoRules::from_fen("1k4r1/8/8/8/8/8/8/1K2R3 w K - 0 1").unwrap();
+        assert!(rules.parse_san("O-O").is_none());
+        let rules = PlecoRules::from_fen("1k3r2/8/8/8/8/8/8/1K2R3 w K - 0 1").unwrap();
+        assert!(rules.parse_san("O-O").is_none());
+        let rules = PlecoRules::from_fen("1k6/8/8/8/8/8/8/1K2R3 w K - 0 1").unwrap();
+        assert!(rules.parse_san("O-O").is_some());
+    }
+
+    #[test]
+    fn keeps_rights_of_unmoved_rooks() {
+        let fen = "rk2r3/pppppppp/8/8/8/8/1PPPPPPP/RK2R3 w KQkq - 0 1";
+        let mut rules = PlecoRules::from_fen(fen).unwrap();
+        assert!(rules.play(&Move::new([0, 0], [0, 3])));
+        assert_eq!(
+            rules.fen(),
+            "rk2r3/pppppppp/8/8/R7/8/1PPPPPPP/1K2R3 b Kkq - 1 1"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Standard algebraic notation, written and read from the legal moves of
+//! any rules backend.
+
+use super::{ChessRules, Move, PieceKind};
+use crate::annotation::{cell_text, parse_cell};
+
+fn file_letter(cell: [u8; 2]) -> char {
+    (b'a' + cell[0]) as char
+}
+
+fn rank_digit(cell: [u8; 2]) -> char {
+    (b'1' + cell[1]) as char
+}
+
+/// The cells of the other pieces of the same kind which could also go to
+/// the target of the move.
+fn rivals_of<R: ChessRules + ?Sized>(
+    rules: &R,
+    legal_moves: &[Move],
+    chess_move: &Move,
+) -> Vec<[u8; 2]> {
+    let piece = rules.piece_at(chess_move.from);
+    let mut res: Vec<[u8; 2]> = Vec::new();
+    for other in legal_moves {
+        let is_rival = other.from != chess_move.from
+            && other.to == chess_move.to
+            && !other.is_castling()
+            && rules.piece_at(other.from) == piece;
+        if is_rival && !res.contains(&other.from) {
+            res.push(other.from);
+        }
+    }
+    res
+}
+
+pub fn move_to_san<R: ChessRules + ?Sized>(rules: &R, chess_move: &Move) -> Option<String> {
+    let legal_moves = rules.legal_moves();
+    if !legal_moves.contains(chess_move) {
+        return None;
+    }
+
+    let mut res = String::new();
+    if let Some(rook_cell) = chess_move.castling_rook {
+        res.push_str(if rook_cell[0] > chess_move.from[0] {
+            "O-O"
+        } else {
+            "O-O-O"
+        });
+    } else {
+        let kind = rules.piece_at(chess_move.from)?.kind();
+        let capture = rules.is_capture(chess_move);
+        if kind == PieceKind::Pawn {
+            if capture {
+                res.push(file_letter(chess_move.from));
+            }
+        } else {
+            res.push(kind.letter());
+            let rivals = rivals_of(rules, &legal_moves, chess_move);
+            if !rivals.is_empty() {
+                let same_file = rivals.iter().any(|cell| cell[0] == chess_move.from[0]);
+                let same_rank = rivals.iter().any(|cell| cell[1] == chess_move.from[1]);
+                if !same_file {
+                    res.push(file_letter(chess_move.from));
+                } else if !same_rank {
+                    res.push(rank_digit(chess_move.from));
+                } else {
+                    res.push_str(&cell_text(chess_move.from));
+                }
+            }
+        }
+        if capture {
+            res.push('x');
+        }
+        res.push_str(&cell_text(chess_move.to));
+        if let Some(promotion) = chess_move.promotion {
+            res.push('=');
+            res.push(promotion.letter());
+        }
+    }
+
+    let mut after = rules.clone_box();
+    after.play(chess_move);
+    if after.is_checkmate() {
+        res.push('#');
+    } else if after.is_check() {
+        res.push('+');
+    }
+    Some(res)
+}
+
+pub fn parse_san<R: ChessRules + ?Sized>(rules: &R, text: &str) -> Option<Move> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal_moves = rules.legal_moves();
+
+    let castling = match text {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(short) = castling {
+        return legal_moves.into_iter().find(|legal_move| {
+            legal_move
+                .castling_rook
+                .is_some_and(|rook_cell| (rook_cell[0] > legal_move.from[0]) == short)
+        });
+    }
+
+    let mut letters: Vec<char> = text
+        .chars()
+        .filter(|letter| !matches!(letter, 'x' | ':' | '-' | '='))
+        .collect();
+    // The target cell ends with a digit, so a last letter is a promotion.
+    let promotion = match letters.last() {
+        Some(letter) if letter.is_ascii_alphabetic() => {
+            let promotion = PieceKind::from_letter(*letter)?;
+            letters.pop();
+            Some(promotion)
+        }
+        _ => None,
+    };
+    let kind = match letters.first() {
+        Some(letter) if letter.is_ascii_uppercase() => {
+            let kind = PieceKind::from_letter(*letter)?;
+            letters.remove(0);
+            kind
+        }
+        _ => PieceKind::Pawn,
+    };
+    if letters.len() < 2 {
+        return None;
+    }
+    let target_text: String = letters[letters.len() - 2..].iter().collect();
+    let target = parse_cell(&target_text)?;
+    let hints = &letters[..letters.len() - 2];
+    let file_hint = hints
+        .iter()
+        .find(|letter| ('a'..='h').contains(*letter))
+        .map(|letter| *letter as u8 - b'a');
+    let rank_hint = hints
+        .iter()
+        .find(|letter| ('1'..='8').contains(*letter))
+        .map(|letter| *letter as u8 - b'1');
+
+    let mut candidates = legal_moves.into_iter().filter(|legal_move| {
+        !legal_move.is_castling()
+            && legal_move.to == target
+            && legal_move.promotion == promotion
+            && rules
+                .piece_at(legal_move.from)
+                .is_some_and(|piece| piece.kind() == kind)
+            && file_hint.map_or(true, |file| legal_move.from[0] == file)
+            && rank_hint.map_or(true, |rank| legal_move.from[1] == rank)
+    });
+    let res = candidates.next()?;
+    if candidates.next().is_some() {
+        // Ambiguous.
+        return None;
+    }
+    Some(res)
+}